@@ -1,7 +1,7 @@
 #![allow(dead_code)] // NOTE: this is just quick testing
 
 use colored::*;
-use searcher::{search, sorted_take, stylize};
+use searcher::{search, sorted_take, stylize, SearchMode};
 use std::{
     env, fs,
     io::{self, Write},
@@ -56,7 +56,8 @@ fn main() {
         //     gcast::searcher::compile::compile_search_term_to_strings(&search_term)
         // );
         let search_prev = Instant::now();
-        if let Ok(mut search_res) = search(&search_term, &file_lines) {
+        if let Ok(mut search_res) = search(&search_term, SearchMode::Swiper, &file_lines)
+        {
             println!("{} {:?}", "search time:".blue(), search_prev.elapsed());
 
             let sort_prev = Instant::now();
@@ -3,8 +3,11 @@ use protocol::{
     to_client::{seat, ToClient},
     to_server::{
         fscontrol::{search_ctrl, tree_ctrl},
-        fsstart, mpvcontrol, mpvstart, sendstatus, spotifystart,
+        fsstart, mpvcontrol,
+        mpvstart::{self, options::MpvOptions},
+        sendstatus, spotifystart,
     },
+    util::FileId,
     ToServerable,
 };
 use tungstenite::connect;
@@ -29,7 +32,7 @@ enum Commands {
     FilerCd { i: usize },
     FilerCdUp,
     MpvPlayUrl { url: String },
-    MpvPlayFile { root: usize, path: String },
+    MpvPlayFile { index: usize, generation: u64 },
     MpvStop,
     MpvPause,
 }
@@ -70,16 +73,21 @@ fn main() {
         Commands::FilerStop => fsstart::Stop.to_server(),
         Commands::FilerRefreshCache => fsstart::RefreshCache.to_server(),
         Commands::FilerSearch { query } => {
-            search_ctrl::Search(query.to_string()).to_server()
+            search_ctrl::Search(search_ctrl::search::Search {
+                query: query.to_string(),
+                mode: Default::default(),
+            })
+            .to_server()
         }
         Commands::MpvPlayUrl { url } => mpvstart::url::Url {
             url: url.clone(),
-            paused: false,
+            quality: mpvstart::url::Quality::Default,
+            options: MpvOptions::new(),
         }
         .to_server(),
-        Commands::MpvPlayFile { root, path } => mpvstart::file::File {
-            root: *root,
-            path: path.to_string(),
+        Commands::MpvPlayFile { index, generation } => mpvstart::file::File {
+            id: FileId::new(*index, *generation),
+            options: MpvOptions::new(),
         }
         .to_server(),
         Commands::MpvStop => mpvstart::Stop.to_server(),
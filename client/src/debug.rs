@@ -107,6 +107,7 @@ impl Debug {
         let subpage = self.path.get(1).map(String::as_str);
         let toclient = match subpage {
             Some("play") => playstate::PlayState {
+                seq: 0,
                 title: self.string_kv("title", "Exempelvideofilm"),
                 pause: self.bool_kv("pause", false),
                 progress: Duration::from_secs(1111),
@@ -115,7 +116,19 @@ impl Debug {
                     Percent::<Positive>::new(self.f64_kv("volume", 80.0))
                         .unwrap_or_default()
                 }),
-                chapter: None,
+                chapter: self.bool_kv("chapters", false).then_some((2, 3)),
+                chapters: if self.bool_kv("chapters", false) {
+                    ["Intro", "Episode", "Credits"]
+                        .iter()
+                        .zip([0, 90, 3500])
+                        .map(|(title, start)| playstate::Chapter {
+                            title: title.to_string(),
+                            start: Some(Duration::from_secs(start)),
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                },
                 subtitles: vec![Track {
                     id: 0,
                     title: "None".to_string(),
@@ -126,6 +139,31 @@ impl Debug {
                     title: "None".to_string(),
                     selected: true,
                 }],
+                tweaks: playstate::Tweaks {
+                    deinterlace: self.bool_kv("deinterlace", false),
+                    loop_file: self.bool_kv("loop", false),
+                    ..Default::default()
+                },
+                skip: self
+                    .bool_kv("intro", false)
+                    .then_some(playstate::Segment::Intro),
+            }
+            .to_client(),
+            Some("queue") => mpv::queue::Queue {
+                entries: vec![
+                    mpv::queue::Entry::File {
+                        root: 0,
+                        name: "Exempelvideofilm.mkv".to_string(),
+                    },
+                    mpv::queue::Entry::Url {
+                        url: "https://example.com/video".to_string(),
+                    },
+                ],
+            }
+            .to_client(),
+            Some("resume") => mpv::resume::Resume {
+                name: "Exempelvideofilm.mkv".to_string(),
+                position: Duration::from_secs(1421),
             }
             .to_client(),
             _ => mpv::Load.to_client(),
@@ -133,7 +171,9 @@ impl Debug {
 
         match toclient {
             protocol::ToClient::Front(front) => front,
-            protocol::ToClient::Seat(_) => unreachable!("will always be a front"),
+            protocol::ToClient::Seat(_) | protocol::ToClient::Preview(_) => {
+                unreachable!("will always be a front")
+            }
         }
     }
 }
@@ -2,12 +2,18 @@ use super::visibility::use_page_visibility;
 use super::websocket;
 use super::websocket::use_websocket;
 use crate::debug;
+use crate::preview::{data_url, SpriteSheet, Thumbnails};
 use derivative::Derivative;
 use protocol::to_client;
+use protocol::to_client::front::mpv::{self, playstate::PlayState};
 use protocol::to_client::front::Front;
+use protocol::to_client::preview::Preview;
 use protocol::to_client::seat::Seat;
+use std::rc::Rc;
 use yew::hook;
 use yew::use_effect_with;
+use yew::use_mut_ref;
+use yew::use_state;
 use yew::use_state_eq;
 
 #[derive(Clone, Derivative)]
@@ -16,6 +22,8 @@ pub struct UseServer {
     front: Front,
     connected: bool,
     accepted: Accepted,
+    thumbnails: Rc<Thumbnails>,
+    sprites: Option<Rc<SpriteSheet>>,
     #[derivative(PartialEq = "ignore")]
     sender: websocket::Sender,
 }
@@ -50,6 +58,15 @@ impl UseServer {
     pub fn sender(&self) -> Sender {
         Sender(self.sender.clone())
     }
+
+    pub fn thumbnails(&self) -> Rc<Thumbnails> {
+        self.thumbnails.clone()
+    }
+
+    /// The sprites of what is currently playing, if they have been received
+    pub fn sprites(&self) -> Option<Rc<SpriteSheet>> {
+        self.sprites.clone()
+    }
 }
 
 impl Sender {
@@ -94,11 +111,18 @@ pub fn use_server() -> UseServer {
     }
 
     let front = use_state_eq(|| Front::None);
+    // The last play state, which the patches are applied to
+    let base = use_mut_ref(|| None::<PlayState>);
     let accepted = use_state_eq(|| Accepted::Pending);
+    let thumbnails = use_state(|| Rc::new(Thumbnails::new()));
+    let sprites = use_state_eq(|| None::<Rc<SpriteSheet>>);
 
     {
         let front = front.clone();
+        let base = base.clone();
         let accepted = accepted.clone();
+        let thumbnails = thumbnails.clone();
+        let sprites = sprites.clone();
         let ws = ws.clone();
         let sender = Sender(ws.sender());
         use_effect_with(ws.message(), move |bytes| {
@@ -108,9 +132,56 @@ pub fn use_server() -> UseServer {
                     Ok(protocol::Message::ToServer(msg)) => {
                         log::error!("Got a message for the server: {:?}", msg)
                     }
+                    Ok(protocol::Message::ToClient(to_client::Front(Front::Mpv(
+                        mpv::PlayStatePatch(patch),
+                    )))) => {
+                        let mut base = base.borrow_mut();
+                        match &mut *base {
+                            Some(state) if state.seq + 1 == patch.seq => {
+                                state.apply(patch);
+                                front.set(mpv::PlayState(state.clone()).into());
+                            }
+                            Some(state) => {
+                                log::warn!(
+                                    "Missed the patches between {} and {}, asking for a full state",
+                                    state.seq,
+                                    patch.seq
+                                );
+                                sender.send(protocol::to_server::sendstatus::SendStatus);
+                            }
+                            None => {
+                                log::warn!(
+                                    "Got a patch without a full state, asking for one"
+                                );
+                                sender.send(protocol::to_server::sendstatus::SendStatus);
+                            }
+                        }
+                    }
                     Ok(protocol::Message::ToClient(to_client::Front(new_front))) => {
+                        *base.borrow_mut() = match &new_front {
+                            Front::Mpv(mpv::PlayState(state)) => Some(state.clone()),
+                            _ => None,
+                        };
+                        if !matches!(new_front, Front::Mpv(_)) {
+                            sprites.set(None);
+                        }
                         front.set(new_front);
                     }
+                    Ok(protocol::Message::ToClient(to_client::Preview(preview))) => {
+                        match preview {
+                            Preview::Thumbnail(thumbnail) => {
+                                let mut new = (**thumbnails).clone();
+                                new.insert(
+                                    thumbnail.id,
+                                    thumbnail.jpeg.as_deref().map(data_url),
+                                );
+                                thumbnails.set(Rc::new(new));
+                            }
+                            Preview::Sprites(new) => {
+                                sprites.set(Some(Rc::new(SpriteSheet::new(new))));
+                            }
+                        }
+                    }
                     Ok(protocol::Message::ToClient(to_client::Seat(seat))) => {
                         match seat {
                             Seat::Accept => {
@@ -131,6 +202,8 @@ pub fn use_server() -> UseServer {
         front: (*front).clone(),
         connected: ws.is_connected(),
         accepted: *accepted,
+        thumbnails: (*thumbnails).clone(),
+        sprites: (*sprites).clone(),
         sender: ws.sender(),
     }
 }
@@ -141,6 +214,8 @@ pub fn use_server_debug(debug: &debug::Debug) -> UseServer {
         front: debug.front(),
         connected: debug.is_connected(),
         accepted: debug.accepted(),
+        thumbnails: Rc::default(),
+        sprites: None,
         sender: websocket::Sender::empty(),
     }
 }
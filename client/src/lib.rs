@@ -24,6 +24,7 @@ mod mpv;
 mod nothing;
 mod pending;
 mod playurl;
+mod preview;
 mod progressbar;
 mod rejected;
 mod search;
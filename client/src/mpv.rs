@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use protocol::{
     to_client::front::mpv as prot,
-    to_server::{mpvcontrol, mpvstart},
+    to_server::{mpvcontrol, mpvstart, previewreq, queuectrl, resumectrl},
     util::{Normal, Percent},
 };
 use yew::prelude::*;
@@ -21,6 +21,16 @@ pub struct MpvProps {
 #[rustfmt::skip::macros(html)]
 #[function_component(Mpv)]
 pub fn mpv(props: &MpvProps) -> Html {
+    match &props.front {
+        prot::Queue(queue) => html! {<Queue front={queue.clone()} />},
+        prot::Resume(resume) => html! {<Resume front={resume.clone()} />},
+        front => html! {<Player front={front.clone()} />},
+    }
+}
+
+#[rustfmt::skip::macros(html)]
+#[function_component(Player)]
+fn player(props: &MpvProps) -> Html {
     let server = use_context::<UseServer>().expect("no server context found");
     let clickable = server.is_connected() && !matches!(props.front, prot::Mpv::Load);
 
@@ -32,6 +42,23 @@ pub fn mpv(props: &MpvProps) -> Html {
     let subtitles = subtitles(&props.front);
     let audios = audios(&props.front);
     let volume = volume(&props.front);
+    let tweaks = tweaks(&props.front);
+    let chapter_list = chapter_list(&props.front);
+    let markers = chapter_markers(&props.front);
+    let skip = skip_label(&props.front);
+    let ab_looping = tweaks.ab_loop != prot::playstate::AbLoop::Off;
+    let sprites = server.sprites();
+
+    {
+        // NOTE: they are only sent to the clients that ask, once they are done
+        let sender = server.sender();
+        let missing = matches!(props.front, prot::PlayState(_)) && sprites.is_none();
+        use_effect_with(missing, move |missing| {
+            if *missing {
+                sender.send(previewreq::Sprites);
+            }
+        });
+    }
 
     let on_slide = {
         let sender = server.sender();
@@ -57,9 +84,20 @@ pub fn mpv(props: &MpvProps) -> Html {
                              disabled={!clickable}
                              on_slide={on_slide}
                              progress={progress(&props.front)}
+                             markers={markers}
+                             preview={sprites}
+                             length={length(&props.front)}
                              outer_class={classes!("mpv-progress-outer")}
                              inner_class={classes!("mpv-progress-inner")}/>
             </div>
+            if let Some(skip) = skip {
+                <div class={classes!("pad", "fill-nicely", "gap")}>
+                    <button onclick={click_send!(server, mpvcontrol::SkipSegment)}
+                            disabled={!clickable}>
+                        {skip}
+                    </button>
+                </div>
+            }
             <div class={classes!("space-evenly", "pad")}>
                 <button onclick={click_send!(server, mpvcontrol::PrevChapter)}
                         class={classes!("round", "icon", "icon-skip-back")}
@@ -89,6 +127,15 @@ pub fn mpv(props: &MpvProps) -> Html {
                         class={classes!("round", "icon", "icon-skip-fwd")}
                         disabled={!clickable || !has_chapters} />
             </div>
+            if !chapter_list.is_empty() {
+                <div class={classes!("section", "pad", "small")}>
+                    <span>{"Chapters"}</span>
+                </div>
+                <TrackSelector
+                    tracks={chapter_list}
+                    disabled={!clickable}
+                    onclick={click_send!(server, i -> mpvcontrol::SetChapter(i))} />
+            }
             <div class={classes!("section", "pad", "small")}>
                 <span>{"Subtitle controls"}</span>
             </div>
@@ -140,6 +187,189 @@ pub fn mpv(props: &MpvProps) -> Html {
             <TrackSelector tracks={audios}
                            disabled={!clickable}
                            onclick={click_send!(server, id -> mpvcontrol::SetAudio(id))} />
+            <div class={classes!("section", "pad", "small")}>
+                <span>{"Playback controls"}</span>
+                <span class={classes!("float-right")}>
+                    {format!("Speed: {:.0}%, audio delay: {} ms",
+                             tweaks.speed.as_f64(), tweaks.audio_delay)}
+                </span>
+            </div>
+            <div class={classes!("space-evenly", "pad")}>
+                <button onclick={click_send!(server, mpvcontrol::SpeedDown)}
+                        class={classes!("round", "icon", "icon-remove")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::SpeedReset)}
+                        class={classes!("round", "icon", "icon-renew")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::SpeedUp)}
+                        class={classes!("round", "icon", "icon-add")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::AudioDelayEarlier)}
+                        class={classes!("round", "icon", "icon-back-arrow")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::AudioDelayLater)}
+                        class={classes!("round", "icon", "icon-forward-arrow")}
+                        disabled={!clickable} />
+            </div>
+            <div class={classes!("pad", "fill-nicely", "gap")}>
+                <button onclick={click_send!(server, mpvcontrol::AbLoop)}
+                        class={classes!(inverted(!ab_looping))}
+                        disabled={!clickable}>
+                    {ab_loop(&tweaks.ab_loop)}
+                </button>
+                <button onclick={click_send!(server, mpvcontrol::ToggleLoopFile)}
+                        class={classes!(inverted(!tweaks.loop_file))}
+                        disabled={!clickable}>
+                    {"Loop file"}
+                </button>
+            </div>
+            <div class={classes!("section", "pad", "small")}>
+                <span>{"Video controls"}</span>
+                <span class={classes!("float-right")}>
+                    {format!("Zoom: {:.0}%", tweaks.zoom.as_f64())}
+                </span>
+            </div>
+            <div class={classes!("space-evenly", "pad")}>
+                <button onclick={click_send!(server, mpvcontrol::ZoomOut)}
+                        class={classes!("round", "icon", "icon-remove")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::ZoomIn)}
+                        class={classes!("round", "icon", "icon-add")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::PanLeft)}
+                        class={classes!("round", "icon", "icon-back-arrow")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::PanRight)}
+                        class={classes!("round", "icon", "icon-forward-arrow")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::PanUp)}
+                        class={classes!("round", "icon", "icon-up-arrow")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::PanDown)}
+                        class={classes!("round", "icon", "icon-down-arrow")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, mpvcontrol::ResetVideo)}
+                        class={classes!("round", "icon", "icon-renew")}
+                        disabled={!clickable} />
+            </div>
+            <div class={classes!("pad", "fill-nicely", "gap")}>
+                <button onclick={click_send!(server, mpvcontrol::CycleAspect)}
+                        class={classes!(inverted(tweaks.aspect.is_none()))}
+                        disabled={!clickable}>
+                    {format!("Aspect: {}", tweaks.aspect.as_deref().unwrap_or("original"))}
+                </button>
+                <button onclick={click_send!(server, mpvcontrol::ToggleDeinterlace)}
+                        class={classes!(inverted(!tweaks.deinterlace))}
+                        disabled={!clickable}>
+                    {"Deinterlace"}
+                </button>
+            </div>
+            <div class={classes!("section", "pad", "small")}>
+                <span>{"Queue"}</span>
+            </div>
+            <div class={classes!("space-evenly", "pad")}>
+                <button onclick={click_send!(server, queuectrl::Show)}
+                        class={classes!("round", "icon", "icon-queue")}
+                        disabled={!clickable} />
+
+                <button onclick={click_send!(server, queuectrl::Skip)}
+                        class={classes!("round", "icon", "icon-skip-next")}
+                        disabled={!clickable} />
+            </div>
+        </article>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct QueueProps {
+    front: prot::queue::Queue,
+}
+
+#[rustfmt::skip::macros(html)]
+#[function_component(Queue)]
+fn queue(props: &QueueProps) -> Html {
+    let server = use_context::<UseServer>().expect("no server context found");
+    let last = props.front.entries.len().saturating_sub(1);
+
+    let entries: Html = props
+        .front
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let (name, color) = match entry {
+                prot::queue::Entry::File { name, .. } => (name, "dracula-green"),
+                prot::queue::Entry::Url { url } => (url, "dracula-cyan"),
+            };
+            html! {
+                <div class={classes!("search-res")}>
+                    <span class={classes!("search-detail", color)}></span>
+                    <span class={classes!("search-content")}>{name}</span>
+                    <span class={classes!("queue-actions")}>
+                        <button onclick={click_send!(server, queuectrl::MoveUp(i))}
+                                class={classes!("icon", "icon-up-arrow")}
+                                disabled={server.is_disconnected() || i == 0} />
+                        <button onclick={click_send!(server, queuectrl::MoveDown(i))}
+                                class={classes!("icon", "icon-down-arrow")}
+                                disabled={server.is_disconnected() || i == last} />
+                        <button onclick={click_send!(server, queuectrl::Remove(i))}
+                                class={classes!("icon", "icon-close", "error")}
+                                disabled={server.is_disconnected()} />
+                    </span>
+                </div>
+            }
+        })
+        .collect();
+
+    html! {
+        <article class={classes!("stacker")}>
+            <BackButton button_type={Type::Back}
+                        onclick={click_send!(server, queuectrl::Hide)} />
+            if props.front.entries.is_empty() {
+                <div class={classes!("pad")}>{"The queue is empty"}</div>
+            }
+            <div class={classes!("rows")}>
+                {entries}
+            </div>
+        </article>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ResumeProps {
+    front: prot::resume::Resume,
+}
+
+#[rustfmt::skip::macros(html)]
+#[function_component(Resume)]
+fn resume(props: &ResumeProps) -> Html {
+    let server = use_context::<UseServer>().expect("no server context found");
+
+    html! {
+        <article class={classes!("stacker")}>
+            <BackButton button_type={Type::Back}
+                        onclick={click_send!(server, mpvstart::Stop)} />
+            <div class={classes!("pad")}>
+                <div class={classes!("kinda-big", "mpv-title")}>{&props.front.name}</div>
+            </div>
+            <button disabled={server.is_disconnected()}
+                    onclick={click_send!(server, resumectrl::Resume)}>
+                {format!("Resume from {}", timestamp(props.front.position.as_secs_f64()))}
+            </button>
+            <button disabled={server.is_disconnected()}
+                    onclick={click_send!(server, resumectrl::StartOver)}>
+                {"Start over"}
+            </button>
         </article>
     }
 }
@@ -198,6 +428,57 @@ fn has_chapters(front: &prot::Mpv) -> bool {
     )
 }
 
+/// The chapters as tracks, so that they can be shown with a [`TrackSelector`]
+fn chapter_list(front: &prot::Mpv) -> Vec<prot::playstate::Track> {
+    match front {
+        prot::PlayState(prot::playstate::PlayState {
+            chapter, chapters, ..
+        }) => chapters
+            .iter()
+            .zip(0..)
+            .map(|(c, i)| prot::playstate::Track {
+                id: i,
+                title: match c.start {
+                    Some(start) => {
+                        format!("{} {}", timestamp(start.as_secs_f64()), c.title)
+                    }
+                    None => c.title.clone(),
+                },
+                selected: chapter.is_some_and(|(current, _)| current == i + 1),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn chapter_markers(front: &prot::Mpv) -> Vec<Percent<Normal>> {
+    match front {
+        prot::PlayState(prot::playstate::PlayState {
+            chapters, length, ..
+        }) => chapters
+            .iter()
+            .filter_map(|c| c.start)
+            .filter(|start| !start.is_zero())
+            .filter_map(|start| {
+                Percent::<Normal>::of(start.as_secs_f64(), length.as_secs_f64())
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn skip_label(front: &prot::Mpv) -> Option<&'static str> {
+    match front {
+        prot::PlayState(prot::playstate::PlayState { skip, .. }) => {
+            skip.as_ref().map(|segment| match segment {
+                prot::playstate::Segment::Intro => "Skip intro",
+                prot::playstate::Segment::Outro => "Skip outro",
+            })
+        }
+        _ => None,
+    }
+}
+
 fn progress(front: &prot::Mpv) -> Percent<Normal> {
     match *front {
         prot::PlayState(prot::playstate::PlayState {
@@ -208,6 +489,13 @@ fn progress(front: &prot::Mpv) -> Percent<Normal> {
     }
 }
 
+fn length(front: &prot::Mpv) -> Duration {
+    match front {
+        prot::PlayState(state) => state.length,
+        _ => Duration::ZERO,
+    }
+}
+
 fn volume(front: &prot::Mpv) -> String {
     match front {
         prot::PlayState(prot::playstate::PlayState {
@@ -220,7 +508,9 @@ fn volume(front: &prot::Mpv) -> String {
 
 fn progress_timestamps(front: &prot::Mpv) -> (String, String) {
     match front {
-        prot::Load => ("0".to_string(), "0".to_string()),
+        prot::Load | prot::PlayStatePatch(_) | prot::Queue(_) | prot::Resume(_) => {
+            ("0".to_string(), "0".to_string())
+        }
         prot::PlayState(prot::playstate::PlayState {
             progress, length, ..
         }) => (
@@ -244,14 +534,18 @@ fn timestamp(seconds: f64) -> String {
 
 fn title(front: &prot::Mpv) -> &str {
     match front {
-        prot::Load => "Loading...",
+        prot::Load | prot::PlayStatePatch(_) | prot::Queue(_) | prot::Resume(_) => {
+            "Loading..."
+        }
         prot::PlayState(prot::playstate::PlayState { title, .. }) => title,
     }
 }
 
 fn play_icon(front: &prot::Mpv) -> Vec<&'static str> {
     match front {
-        prot::Load => vec!["icon-renew", "spin"],
+        prot::Load | prot::PlayStatePatch(_) | prot::Queue(_) | prot::Resume(_) => {
+            vec!["icon-renew", "spin"]
+        }
         prot::PlayState(prot::playstate::PlayState { pause: true, .. }) => {
             vec!["icon-play"]
         }
@@ -263,24 +557,54 @@ fn play_icon(front: &prot::Mpv) -> Vec<&'static str> {
 
 fn subtitles(front: &prot::Mpv) -> Vec<prot::playstate::Track> {
     match front {
-        prot::Load => vec![prot::playstate::Track {
-            id: 0,
-            selected: true,
-            title: "Loading...".to_string(),
-        }],
+        prot::Load | prot::PlayStatePatch(_) | prot::Queue(_) | prot::Resume(_) => {
+            vec![prot::playstate::Track {
+                id: 0,
+                selected: true,
+                title: "Loading...".to_string(),
+            }]
+        }
         prot::PlayState(prot::playstate::PlayState { subtitles, .. }) => {
             subtitles.clone()
         }
     }
 }
 
+fn tweaks(front: &prot::Mpv) -> prot::playstate::Tweaks {
+    match front {
+        prot::PlayState(prot::playstate::PlayState { tweaks, .. }) => tweaks.clone(),
+        _ => prot::playstate::Tweaks::default(),
+    }
+}
+
+fn ab_loop(ab_loop: &prot::playstate::AbLoop) -> String {
+    match ab_loop {
+        prot::playstate::AbLoop::Off => "A-B loop".to_string(),
+        prot::playstate::AbLoop::A(a) => {
+            format!("A-B loop: {}-", timestamp(a.as_secs_f64()))
+        }
+        prot::playstate::AbLoop::AB(a, b) => format!(
+            "A-B loop: {}-{}",
+            timestamp(a.as_secs_f64()),
+            timestamp(b.as_secs_f64())
+        ),
+    }
+}
+
+/// Buttons that are not on are shown inverted
+fn inverted(off: bool) -> Option<&'static str> {
+    off.then_some("inverted")
+}
+
 fn audios(front: &prot::Mpv) -> Vec<prot::playstate::Track> {
     match front {
-        prot::Load => vec![prot::playstate::Track {
-            id: 0,
-            selected: true,
-            title: "Loading...".to_string(),
-        }],
+        prot::Load | prot::PlayStatePatch(_) | prot::Queue(_) | prot::Resume(_) => {
+            vec![prot::playstate::Track {
+                id: 0,
+                selected: true,
+                title: "Loading...".to_string(),
+            }]
+        }
         prot::PlayState(prot::playstate::PlayState { audios, .. }) => audios.clone(),
     }
 }
@@ -1,12 +1,24 @@
 use super::UseServer;
-use protocol::to_server::{mpvstart, playurlstart};
+use protocol::to_server::{
+    mpvstart::{self, options::MpvOptions, url::Quality},
+    playurlstart, queuectrl,
+};
 
 use wasm_bindgen::JsCast;
-use web_sys::{window, HtmlDocument, HtmlInputElement};
+use web_sys::{window, HtmlDocument, HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
 use crate::back_button::{BackButton, Type};
 
+/// The qualities that can be picked, by name
+const QUALITIES: &[(&str, Quality)] = &[
+    ("Default quality", Quality::Default),
+    ("At most 1080p", Quality::MaxHeight(1080)),
+    ("At most 720p", Quality::MaxHeight(720)),
+    ("At most 480p", Quality::MaxHeight(480)),
+    ("Audio only", Quality::AudioOnly),
+];
+
 #[rustfmt::skip::macros(html)]
 #[function_component(PlayUrl)]
 pub fn playurl() -> Html {
@@ -30,13 +42,32 @@ pub fn playurl() -> Html {
         })
     };
 
+    let quality = use_state(|| 0);
+    let quality_change = {
+        let quality_setter = quality.setter();
+
+        Callback::from(move |e: Event| {
+            let selected = e
+                .target()
+                .and_then(|target| target.dyn_into().ok())
+                .and_then(|ele: HtmlSelectElement| ele.value().parse().ok());
+
+            match selected {
+                Some(i) => quality_setter.set(i),
+                None => log::error!("Could not get the selected quality"),
+            }
+        })
+    };
+
     let play_click = {
         let url = url.clone();
+        let quality = quality.clone();
         click_send!(
             server,
             mpvstart::url::Url {
                 url: (*url).clone(),
-                paused: false
+                quality: QUALITIES[*quality].1.clone(),
+                options: MpvOptions::new(),
             }
             .clone()
         )
@@ -44,16 +75,31 @@ pub fn playurl() -> Html {
 
     let play_paused_click = {
         let url = url.clone();
+        let quality = quality.clone();
         click_send!(
             server,
             mpvstart::url::Url {
                 url: (*url).clone(),
-                paused: true
+                quality: QUALITIES[*quality].1.clone(),
+                options: MpvOptions::new().paused(true),
             }
             .clone()
         )
     };
 
+    let enqueue_click = {
+        let url = url.clone();
+        let quality = quality.clone();
+        click_send!(
+            server,
+            queuectrl::AddUrl(mpvstart::url::Url {
+                url: (*url).clone(),
+                quality: QUALITIES[*quality].1.clone(),
+                options: MpvOptions::new(),
+            })
+        )
+    };
+
     let paste_click = {
         let input_ref = input_ref.clone();
         Callback::from(move |_| {
@@ -87,6 +133,14 @@ pub fn playurl() -> Html {
                    placeholder={"http://"}
                    disabled={server.is_disconnected()}
             />
+            <select onchange={quality_change}
+                    disabled={server.is_disconnected()}>
+                {for QUALITIES.iter().enumerate().map(|(i, (name, _))| html! {
+                    <option value={i.to_string()} selected={i == *quality}>
+                        {name}
+                    </option>
+                })}
+            </select>
             <button class={classes!()}
                     disabled={server.is_disconnected() || url.is_empty()}
                     onclick={play_click}>
@@ -97,6 +151,11 @@ pub fn playurl() -> Html {
                     onclick={play_paused_click}>
                 {"Play paused"}
             </button>
+            <button class={classes!()}
+                    disabled={server.is_disconnected() || url.is_empty()}
+                    onclick={enqueue_click}>
+                {"Add to queue"}
+            </button>
             <button class={classes!()}
                     disabled={!url.is_empty()}
                     onclick={paste_click}>
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, rc::Rc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use protocol::{
+    to_client::preview::sprites::Sprites, to_server::previewreq, util::FileId,
+};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{Element, IntersectionObserver, IntersectionObserverEntry};
+use yew::prelude::*;
+
+use crate::hooks::server::UseServer;
+
+/// The thumbnails received so far, `None` for the files that have none
+pub type Thumbnails = HashMap<FileId, Option<AttrValue>>;
+
+/// Make something that can be used as the `src` of an image
+pub fn data_url(jpeg: &[u8]) -> AttrValue {
+    let url: Rc<str> = format!("data:image/jpeg;base64,{}", STANDARD.encode(jpeg)).into();
+    AttrValue::from(url)
+}
+
+/// The sprites of what is playing, ready to be shown
+#[derive(PartialEq, Debug)]
+pub struct SpriteSheet {
+    layout: Sprites,
+    url: AttrValue,
+}
+
+impl SpriteSheet {
+    pub fn new(mut sprites: Sprites) -> Self {
+        let url = data_url(&sprites.jpeg);
+        sprites.jpeg = Vec::new();
+        Self {
+            layout: sprites,
+            url,
+        }
+    }
+}
+
+type Observed = (IntersectionObserver, Closure<dyn Fn(Vec<JsValue>)>);
+
+/// Call `on_visible` whenever the element in `node` comes into view, until the observer
+/// is disconnected
+fn observe(node: &NodeRef, on_visible: impl Fn() + 'static) -> Option<Observed> {
+    let callback = Closure::<dyn Fn(Vec<JsValue>)>::new(move |entries: Vec<JsValue>| {
+        let visible = entries.into_iter().any(|entry| {
+            entry
+                .unchecked_into::<IntersectionObserverEntry>()
+                .is_intersecting()
+        });
+        if visible {
+            on_visible();
+        }
+    });
+    let observer = match IntersectionObserver::new(callback.as_ref().unchecked_ref()) {
+        Ok(observer) => observer,
+        Err(e) => {
+            log::error!("Could not create an intersection observer: {e:?}");
+            return None;
+        }
+    };
+    let Some(element) = node.cast::<Element>() else {
+        log::error!("Nothing to observe, it is not rendered");
+        return None;
+    };
+    observer.observe(&element);
+    Some((observer, callback))
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ThumbnailProps {
+    pub id: FileId,
+}
+
+/// The thumbnail of `id`, which is asked for once it is scrolled into view
+#[rustfmt::skip::macros(html)]
+#[function_component(Thumbnail)]
+pub fn thumbnail(props: &ThumbnailProps) -> Html {
+    let server = use_context::<UseServer>().expect("no server context found");
+    let node = use_node_ref();
+    let thumbnail = server.thumbnails().get(&props.id).cloned();
+
+    {
+        let sender = server.sender();
+        let missing = thumbnail.is_none();
+        use_effect_with(
+            (node.clone(), props.id, missing),
+            move |(node, id, missing)| {
+                let id = *id;
+                let observed = missing
+                    .then(|| {
+                        observe(node, move || {
+                            sender.send(previewreq::Thumbnails(vec![id]))
+                        })
+                    })
+                    .flatten();
+                move || {
+                    if let Some((observer, _)) = observed {
+                        observer.disconnect();
+                    }
+                }
+            },
+        );
+    }
+
+    match thumbnail {
+        Some(Some(url)) => html! {<img class={classes!("thumbnail")} src={url} />},
+        _ => html! {<span ref={node} />},
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SeekPreviewProps {
+    pub sheet: Rc<SpriteSheet>,
+    pub time: Duration,
+    /// Where on the bar to show it, in percent
+    pub left: f64,
+}
+
+/// The frame at `time`, cut out of the sprite sheet
+#[rustfmt::skip::macros(html)]
+#[function_component(SeekPreview)]
+pub fn seek_preview(props: &SeekPreviewProps) -> Html {
+    let layout = &props.sheet.layout;
+    let Some((column, row)) = layout.tile(props.time) else {
+        return html! {};
+    };
+
+    let outer = format!("left: {}%;", props.left);
+    // NOTE: the offsets are in percent of the tile, so the size of the tiles don't matter
+    let inner = format!(
+        "width: {}%; left: -{}%; top: -{}%;",
+        layout.columns * 100,
+        column * 100,
+        row * 100
+    );
+    html! {
+        <div class={classes!("seek-preview")} style={outer}>
+            <img src={props.sheet.url.clone()} style={inner} />
+        </div>
+    }
+}
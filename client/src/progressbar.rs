@@ -1,8 +1,12 @@
+use std::{rc::Rc, time::Duration};
+
 use protocol::util::{Normal, Percent};
 use wasm_bindgen::JsCast;
-use web_sys::HtmlInputElement;
+use web_sys::{Element, HtmlInputElement};
 use yew::prelude::*;
 
+use crate::preview::{SeekPreview, SpriteSheet};
+
 #[derive(Properties, PartialEq)]
 pub struct ProgressProps {
     pub progress: Percent<Normal>,
@@ -35,6 +39,15 @@ pub struct ProgressInteractiveProps {
     pub inner_class: Classes,
     pub on_slide: Callback<Percent<Normal>>,
     pub disabled: bool,
+    /// Where to draw marks on the bar, such as where the chapters start
+    #[prop_or_default]
+    pub markers: Vec<Percent<Normal>>,
+    /// Shows what is at the position being hovered or slid to
+    #[prop_or_default]
+    pub preview: Option<Rc<SpriteSheet>>,
+    /// The time at the end of the bar, for the preview
+    #[prop_or_default]
+    pub length: Duration,
 }
 
 #[rustfmt::skip::macros(html)]
@@ -42,6 +55,7 @@ pub struct ProgressInteractiveProps {
 pub fn progressbar_interactive(props: &ProgressInteractiveProps) -> Html {
     let down = use_state_eq(|| false);
     let seek = use_state_eq(|| Percent::<Normal>::default());
+    let hover = use_state_eq(|| None::<Percent<Normal>>);
 
     {
         let on_slide = props.on_slide.clone();
@@ -152,7 +166,44 @@ pub fn progressbar_interactive(props: &ProgressInteractiveProps) -> Html {
         })
     };
 
+    let onmousemove = {
+        let hover = hover.setter();
+        Callback::from(move |me: MouseEvent| {
+            let width = me
+                .target()
+                .and_then(|target| target.dyn_into().ok())
+                .map(|ele: Element| ele.client_width());
+            let Some(width) = width else {
+                log::error!("Could not get the width of the range input");
+                return;
+            };
+            hover.set(Percent::<Normal>::of(me.offset_x().into(), width.into()));
+        })
+    };
+
+    let onmouseleave = {
+        let hover = hover.setter();
+        Callback::from(move |_| hover.set(None))
+    };
+
     let progress = if *down { *seek } else { props.progress };
+    let previewed = if *down { Some(*seek) } else { *hover };
+    let preview = match (&props.preview, previewed) {
+        (Some(sheet), Some(at)) if !props.disabled => html! {
+            <SeekPreview sheet={sheet.clone()}
+                         time={props.length.mul_f64(at.as_f64() / 100.0)}
+                         left={at.as_f64()} />
+        },
+        _ => html! {},
+    };
+    let markers: Html = props
+        .markers
+        .iter()
+        .map(|m| {
+            let style = format!("left: {}%;", m.as_f64());
+            html! {<div class="progress-marker" style={style} />}
+        })
+        .collect();
 
     html! {
         <div class={props.outer_class.clone()}>
@@ -171,7 +222,11 @@ pub fn progressbar_interactive(props: &ProgressInteractiveProps) -> Html {
                    ontouchcancel={ontouchcancel}
                    onmousedown={onmousedown}
                    onmouseup={onmouseup}
+                   onmousemove={onmousemove}
+                   onmouseleave={onmouseleave}
             />
+            {markers}
+            {preview}
         </div>
     }
 }
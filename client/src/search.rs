@@ -1,22 +1,36 @@
 use super::UseServer;
-use std::ops::Range;
+use std::{ops::Range, time::Duration};
 
 use protocol::{
     to_client::front::filesearch as prot,
     to_server::{
-        fscontrol::{search_ctrl, tree_ctrl},
-        fsstart, mpvstart,
+        fscontrol::{
+            search_ctrl::{self, search::SearchMode},
+            tree_ctrl, watched_ctrl,
+        },
+        fsstart,
+        mpvstart::{self, options::MpvOptions},
+        queuectrl,
     },
-    util::{Normal, Percent},
+    util::{FileId, Normal, Percent},
 };
 
 use wasm_bindgen::JsCast;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
 use crate::back_button::{BackButton, Type};
+use crate::preview::Thumbnail;
 use crate::progressbar::Progressbar;
 
+/// The search modes that can be picked, by name
+const SEARCH_MODES: &[(&str, SearchMode)] = &[
+    ("Words in order", SearchMode::Swiper),
+    ("Fuzzy", SearchMode::Fuzzy),
+    ("Exact", SearchMode::Literal),
+    ("Regex", SearchMode::Regex),
+];
+
 const COLORS: &[&str] = &[
     "dracula-pink",
     "dracula-purple",
@@ -63,18 +77,31 @@ fn tree(props: &TreeProps) -> Html {
         .contents
         .iter()
         .map(|entry| match entry {
-            prot::tree::Entry::File { path, root, name } => {
-                let path = path.clone();
-                let root = *root;
+            prot::tree::Entry::File {
+                id,
+                name,
+                watched,
+                progress,
+                media,
+            } => {
+                let id = *id;
+                let on_click = click_send!(
+                    server,
+                    mpvstart::file::File {
+                        id,
+                        options: MpvOptions::new()
+                    }
+                );
                 html! {
-                    <div class={classes!("search-res")}
-                         onclick={click_send!(server,
-                             mpvstart::file::File {
-                                 root: root,
-                                 path: path.clone(),
-                             })}>
+                    <div class={classes!("search-res")} onclick={on_click}>
                         <span class={classes!("search-detail", "dracula-green")}></span>
-                        <span class={classes!("search-content")}>{name}</span>
+                        <Thumbnail {id} />
+                        <span class={classes!("search-content")}>
+                            <span>{name}</span>
+                            <MediaInfo media={media.clone()} />
+                            <WatchProgress progress={*progress} />
+                        </span>
+                        <FileActions {id} watched={*watched} />
                     </div>
                 }
             }
@@ -116,6 +143,11 @@ fn tree(props: &TreeProps) -> Html {
                 <div class={classes!("fill-nicely", "kinda-small", "pad", "row-gap")}>
                     {bread}
                 </div>
+                <button class={classes!("icon", "icon-renew", "icon-hspace")}
+                        disabled={server.is_disconnected()}
+                        onclick={click_send!(server, tree_ctrl::Refresh)}>
+                    {"Refresh this directory"}
+                </button>
             }
             <div class={classes!("rows")}>
                 {files}
@@ -135,6 +167,7 @@ fn results(props: &ResultsProps) -> Html {
     let server = use_context::<UseServer>().expect("no server context found");
 
     let query = use_state(|| props.front.query.to_string());
+    let mode = props.front.mode;
     let query_change = {
         let query_setter = query.setter();
         let sender = server.sender();
@@ -148,13 +181,39 @@ fn results(props: &ResultsProps) -> Html {
             match input {
                 Some(inp) => {
                     query_setter.set(inp.clone());
-                    sender.send(search_ctrl::Search(inp));
+                    sender.send(search_ctrl::Search(search_ctrl::search::Search {
+                        query: inp,
+                        mode,
+                    }));
                 }
                 None => log::error!("Could not get value from text input"),
             }
         })
     };
 
+    let mode_change = {
+        let sender = server.sender();
+        let query = (*query).clone();
+
+        Callback::from(move |e: Event| {
+            let selected = e
+                .target()
+                .and_then(|target| target.dyn_into().ok())
+                .and_then(|ele: HtmlSelectElement| ele.value().parse().ok())
+                .filter(|i: &usize| *i < SEARCH_MODES.len());
+
+            match selected {
+                Some(i) => {
+                    sender.send(search_ctrl::Search(search_ctrl::search::Search {
+                        query: query.clone(),
+                        mode: SEARCH_MODES[i].1,
+                    }));
+                }
+                None => log::error!("Could not get the selected search mode"),
+            }
+        })
+    };
+
     let results_html: Html = props
         .front
         .results
@@ -162,7 +221,7 @@ fn results(props: &ResultsProps) -> Html {
         .map(|res| html! {<SearchResult front={res.clone()} />})
         .collect();
 
-    let invalid_class = (!props.front.query_valid).then_some("invalid");
+    let invalid_class = props.front.error.is_some().then_some("invalid");
 
     // TODO: show a loading icon when props.front.query != *query
     html! {
@@ -177,6 +236,17 @@ fn results(props: &ResultsProps) -> Html {
                    autocapitalize={"none"}
                    disabled={server.is_disconnected()}
             />
+            <select onchange={mode_change}
+                    disabled={server.is_disconnected()}>
+                {for SEARCH_MODES.iter().enumerate().map(|(i, (name, m))| html! {
+                    <option value={i.to_string()} selected={*m == mode}>
+                        {name}
+                    </option>
+                })}
+            </select>
+            if let Some(error) = &props.front.error {
+                <QueryErrorView query={props.front.query.clone()} error={error.clone()} />
+            }
             <div class={classes!("rows")}>
                 {results_html}
             </div>
@@ -184,6 +254,43 @@ fn results(props: &ResultsProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+struct QueryErrorProps {
+    query: String,
+    error: prot::results::QueryError,
+}
+
+/// What is wrong with the query, with the wrong part underlined
+#[rustfmt::skip::macros(html)]
+#[function_component(QueryErrorView)]
+fn query_error_view(props: &QueryErrorProps) -> Html {
+    let error = &props.error;
+    let part = |range: Range<usize>| -> String {
+        props
+            .query
+            .chars()
+            .enumerate()
+            .filter(|(i, _)| range.contains(i))
+            .map(|(_, c)| c)
+            .collect()
+    };
+
+    html! {
+        <div class={classes!("search-error", "kinda-small", "pad-small")}>
+            if error.start < error.end {
+                <div class={classes!("search-error-query")}>
+                    {part(0..error.start)}
+                    <span class={classes!("search-error-span")}>
+                        {part(error.start..error.end)}
+                    </span>
+                    {part(error.end..usize::MAX)}
+                </div>
+            }
+            <div>{&error.message}</div>
+        </div>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 struct SearchResultProps {
     front: prot::results::SearchResult,
@@ -206,13 +313,12 @@ fn search_result(props: &SearchResultProps) -> Html {
     );
 
     let on_click = {
-        let root = props.front.root;
-        let path = props.front.path.clone();
+        let id = props.front.id;
         click_send!(
             server,
             mpvstart::file::File {
-                root,
-                path: path.clone(),
+                id,
+                options: MpvOptions::new()
             }
         )
     };
@@ -226,14 +332,131 @@ fn search_result(props: &SearchResultProps) -> Html {
     html! {
         <div class={classes!("search-res")} onclick={on_click}>
             <span class={classes!("search-detail", color_class)}></span>
+            <Thumbnail id={props.front.id} />
             <span class={classes!("search-content")}>
                 <span class={classes!("kinda-small", "italic")}>{dir}</span>
                 <span>{base}</span>
+                <MediaInfo media={props.front.media.clone()} />
+                <WatchProgress progress={props.front.progress} />
             </span>
+            <FileActions id={props.front.id} watched={props.front.watched} />
         </div>
     }
 }
 
+#[derive(Properties, PartialEq)]
+struct FileActionsProps {
+    id: FileId,
+    watched: bool,
+}
+
+/// Buttons for a file that do something else than playing it, so their clicks must not
+/// reach the surrounding row.
+#[rustfmt::skip::macros(html)]
+#[function_component(FileActions)]
+fn file_actions(props: &FileActionsProps) -> Html {
+    let server = use_context::<UseServer>().expect("no server context found");
+    let file = mpvstart::file::File {
+        id: props.id,
+        options: MpvOptions::new(),
+    };
+
+    let on_watched = {
+        let sender = server.sender();
+        let file = file.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.stop_propagation();
+            sender.send(watched_ctrl::Toggle(file.clone()));
+        })
+    };
+
+    let on_enqueue = {
+        let sender = server.sender();
+        Callback::from(move |e: MouseEvent| {
+            e.stop_propagation();
+            sender.send(queuectrl::AddFile(file.clone()));
+        })
+    };
+
+    let (watched_icon, watched_title) = if props.watched {
+        ("icon-watched", "Mark as unwatched")
+    } else {
+        ("icon-unwatched", "Mark as watched")
+    };
+
+    html! {
+        <span class={classes!("file-actions")}>
+            <button class={classes!("icon", watched_icon)}
+                    title={watched_title}
+                    disabled={server.is_disconnected()}
+                    onclick={on_watched} />
+            <button class={classes!("icon", "icon-playlist-add")}
+                    title={"Add to queue"}
+                    disabled={server.is_disconnected()}
+                    onclick={on_enqueue} />
+        </span>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct WatchProgressProps {
+    progress: Option<Percent<Normal>>,
+}
+
+/// How far into a file it was stopped the last time, if it was
+#[rustfmt::skip::macros(html)]
+#[function_component(WatchProgress)]
+fn watch_progress(props: &WatchProgressProps) -> Html {
+    match props.progress {
+        None => html! {},
+        Some(progress) => html! {
+            <Progressbar {progress}
+                         outer_class={classes!("watch-progress-outer")}
+                         inner_class={classes!("watch-progress-inner")} />
+        },
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct MediaInfoProps {
+    media: Option<prot::media::Media>,
+}
+
+/// What the server found in a file, if it has looked
+#[rustfmt::skip::macros(html)]
+#[function_component(MediaInfo)]
+fn media_info(props: &MediaInfoProps) -> Html {
+    let Some(media) = &props.media else {
+        return html! {};
+    };
+
+    let details: Vec<String> = [
+        media.duration.map(duration),
+        media.height.map(|height| format!("{height}p")),
+        media.english_subs.then(|| "English subs".to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if details.is_empty() {
+        return html! {};
+    }
+    html! {
+        <span class={classes!("media-info", "kinda-small")}>{details.join(" · ")}</span>
+    }
+}
+
+/// A rough length, like "1h 5m"
+fn duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => format!("{}s", duration.as_secs()),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
 fn search_result_substr(path: &str, indices: &[usize], char_range: Range<usize>) -> Html {
     let substr: String = path
         .chars()
@@ -322,7 +545,7 @@ fn init(props: &InitProps) -> Html {
         <>
             <BackButton button_type={Type::Back}
                         onclick={click_send!(server, fsstart::Stop)} />
-            <div class={classes!("pad")}>{cache_date(props.front.last_cache_date)}</div>
+            <div class={classes!("pad")}>{cache_date(props.front.last_cache_date, props.front.live)}</div>
             <button disabled={server.is_disconnected()}
                     class={classes!("icon-refresh", "icon", "icon-hspace")}
                     onclick={click_send!(server, fsstart::RefreshCache)}>
@@ -343,8 +566,11 @@ fn init(props: &InitProps) -> Html {
 }
 
 #[rustfmt::skip::macros(html)]
-fn cache_date(time: Option<std::time::SystemTime>) -> Html {
+fn cache_date(time: Option<std::time::SystemTime>, live: bool) -> Html {
     match time {
+        Some(_) if live => html! {
+            <span class={classes!("bold")}>{"Cache is updated live as files change"}</span>
+        },
         Some(st) => {
             let local: chrono::DateTime<chrono::Local> = st.into();
             html! {
@@ -13,6 +13,8 @@ enum_cstr_map! {Command {
     (Add, c"add"),
     (Seek, c"seek"),
     (Quit, c"quit"),
+    (AbLoop, c"ab-loop"),
+    (SubAdd, c"sub-add"),
 }}
 
 impl<T: super::private::HandleState> Handle<T> {
@@ -140,4 +142,26 @@ impl<T: super::private::InitState> Handle<T> {
     pub fn quit(&mut self) -> Cmd<'_, 'static> {
         self.command(Command::Quit, []).into()
     }
+
+    /// Add an external subtitle file without selecting it. The `title` and `lang` are
+    /// shown in the track list, as for the embedded tracks.
+    pub fn sub_add<'a>(
+        &mut self,
+        file: impl Into<SeeString<'a>>,
+        title: Option<&str>,
+        lang: Option<&str>,
+    ) -> Cmd<'_, 'a> {
+        let args = [
+            file.into(),
+            c"auto".into(),
+            title.unwrap_or("").into(),
+            lang.unwrap_or("").into(),
+        ];
+        self.command(Command::SubAdd, args).into()
+    }
+
+    /// Set the A point of the loop, then the B point, and then clear them again
+    pub fn ab_loop(&mut self) -> Cmd<'_, 'static> {
+        self.command(Command::AbLoop, []).into()
+    }
 }
@@ -262,9 +262,11 @@ properties! {
     (Double, Duration, Get duration, Obs observe_duration),
     (Double, Volume, Get volume, Set set_volume, Obs observe_volume, Add add_volume),
     (Int64, Chapters, Get chapters, Obs observe_chapters),
-    (Int64, Chapter, Get chapter, Obs observe_chapter, Add add_chapter),
+    (Int64, Chapter, Get chapter, Set set_chapter, Obs observe_chapter, Add add_chapter),
+    (Node, ChapterList, Get chapter_list, Obs observe_chapter_list),
     (Node, TrackList, Get track_list, Obs observe_track_list),
     (String, YtdlFormat, Set set_ytdl_format),
+    (String, Start, Set set_start),
     (Flag, Fullscreen, Set set_fullscreen),
     (Flag, Mute, Get is_muted, Obs observe_muted, Cyc toggle_mute),
     (Double, SubDelay, Add add_sub_delay),
@@ -272,6 +274,27 @@ properties! {
     (Double, SubPos, Add add_sub_pos),
     (Int64, SubId, Set set_sub),
     (Int64, AudioId, Set set_audio),
+    (String, VideoId, Set set_video),
+    (String, LoopFile, Set set_loop_file, Obs observe_loop_file),
+    (Double, Speed, Set set_speed, Obs observe_speed, Add add_speed),
+    (Double, AudioDelay, Obs observe_audio_delay, Add add_audio_delay),
+    (Double, VideoZoom, Set set_video_zoom, Obs observe_video_zoom, Add add_video_zoom),
+    (Double, VideoPanX, Set set_video_pan_x, Obs observe_video_pan_x, Add add_video_pan_x),
+    (Double, VideoPanY, Set set_video_pan_y, Obs observe_video_pan_y, Add add_video_pan_y),
+    (Double, VideoAspectOverride, Set set_video_aspect_override, Obs observe_video_aspect_override),
+    (Flag, Deinterlace, Obs observe_deinterlace, Cyc toggle_deinterlace),
+    (String, AbLoopA, Obs observe_ab_loop_a),
+    (String, AbLoopB, Obs observe_ab_loop_b),
+    (String, Vo, Set set_vo),
+    (String, Ao, Set set_ao),
+    (String, VoImageFormat, Set set_vo_image_format),
+    (String, VoImageOutdir, Set set_vo_image_outdir),
+    (String, VideoFilter, Set set_video_filter),
+    (String, VdLavcSkipFrame, Set set_vd_lavc_skip_frame),
+    (Int64, Frames, Set set_frames),
+    (Flag, Untimed, Set set_untimed),
+    (Flag, SubVisibility, Set set_sub_visibility),
+    (String, SubAuto, Set set_sub_auto),
     (EnumCstr Idle, Idle, Get get_idle, Set set_idle),
 }
 
@@ -286,8 +309,10 @@ enum_cstr_map! {pub Property {
     (Volume, c"volume"),
     (Chapters, c"chapters"),
     (Chapter, c"chapter"),
+    (ChapterList, c"chapter-list"),
     (TrackList, c"track-list"),
     (YtdlFormat, c"ytdl-format"),
+    (Start, c"start"),
     (Fullscreen, c"fullscreen"),
     (Mute, c"mute"),
     (SubDelay, c"sub-delay"),
@@ -295,6 +320,27 @@ enum_cstr_map! {pub Property {
     (SubPos, c"sub-pos"),
     (SubId, c"sid"),
     (AudioId, c"aid"),
+    (VideoId, c"vid"),
+    (LoopFile, c"loop-file"),
+    (Speed, c"speed"),
+    (AudioDelay, c"audio-delay"),
+    (VideoZoom, c"video-zoom"),
+    (VideoPanX, c"video-pan-x"),
+    (VideoPanY, c"video-pan-y"),
+    (VideoAspectOverride, c"video-aspect-override"),
+    (Deinterlace, c"deinterlace"),
+    (AbLoopA, c"ab-loop-a"),
+    (AbLoopB, c"ab-loop-b"),
+    (Vo, c"vo"),
+    (Ao, c"ao"),
+    (VoImageFormat, c"vo-image-format"),
+    (VoImageOutdir, c"vo-image-outdir"),
+    (VideoFilter, c"vf"),
+    (VdLavcSkipFrame, c"vd-lavc-skipframe"),
+    (Frames, c"frames"),
+    (Untimed, c"untimed"),
+    (SubVisibility, c"sub-visibility"),
+    (SubAuto, c"sub-auto"),
     (Config, c"config"),
     (ConfigDir, c"config-dir"),
     (Idle, c"idle"),
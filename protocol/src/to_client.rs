@@ -1,8 +1,10 @@
 pub mod front;
+pub mod preview;
 pub mod seat;
 
 #[protocol_macros::message_aggregator]
 enum ToClient {
     Seat(seat::Seat),
     Front(front::Front),
+    Preview(preview::Preview),
 }
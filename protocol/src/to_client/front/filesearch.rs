@@ -1,4 +1,5 @@
 pub mod init;
+pub mod media;
 pub mod refreshing;
 pub mod results;
 pub mod tree;
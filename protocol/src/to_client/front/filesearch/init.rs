@@ -5,4 +5,6 @@ use protocol_macros::message_part;
 #[message_part]
 struct Init {
     last_cache_date: Option<SystemTime>,
+    // The cache is kept up to date as files change, so the date is not interesting
+    live: bool,
 }
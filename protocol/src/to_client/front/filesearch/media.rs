@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+use protocol_macros::message_part;
+
+// What the server found in a file when it probed it
+#[message_part]
+struct Media {
+    duration: Option<Duration>,
+    // The height of the video, e.g. 1080
+    height: Option<u32>,
+    english_subs: bool,
+}
@@ -1,16 +1,40 @@
 use protocol_macros::message_part;
 
+use crate::{
+    to_server::fscontrol::search_ctrl::search::SearchMode,
+    util::{FileId, Normal, Percent},
+};
+
+use super::media::Media;
+
 #[message_part]
 struct Results {
     results: Vec<SearchResult>,
     query: String,
-    query_valid: bool,
+    // The mode the query was matched in
+    mode: SearchMode,
+    error: Option<QueryError>,
+}
+
+// Why the query could not be used
+#[message_part]
+struct QueryError {
+    message: String,
+    // The characters in the query that are wrong, empty if it isn't any part in particular
+    start: usize,
+    end: usize,
 }
 
 #[message_part]
 struct SearchResult {
+    // Only for showing, use the id to refer to the file
     path: String,
+    id: FileId,
     root: usize,
     indices: Vec<usize>,
     basename: usize,
+    watched: bool,
+    progress: Option<Percent<Normal>>,
+    // None if the file has not been probed
+    media: Option<Media>,
 }
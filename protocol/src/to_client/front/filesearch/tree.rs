@@ -1,5 +1,9 @@
 use protocol_macros::message_part;
 
+use crate::util::{FileId, Normal, Percent};
+
+use super::media::Media;
+
 #[message_part]
 struct Tree {
     breadcrumbs: Vec<String>,
@@ -9,9 +13,11 @@ struct Tree {
 #[message_part]
 enum Entry {
     File {
-        path: String,
-        root: usize,
+        id: FileId,
         name: String,
+        watched: bool,
+        progress: Option<Percent<Normal>>,
+        media: Option<Media>,
     },
     Dir {
         name: String,
@@ -1,4 +1,6 @@
 pub mod playstate;
+pub mod queue;
+pub mod resume;
 
 use crate::to_client::ToClient;
 use protocol_macros::message_aggregator;
@@ -7,4 +9,8 @@ use protocol_macros::message_aggregator;
 enum Mpv {
     Load,
     PlayState(playstate::PlayState),
+    // Only what changed since the last PlayState
+    PlayStatePatch(playstate::Patch),
+    Queue(queue::Queue),
+    Resume(resume::Resume),
 }
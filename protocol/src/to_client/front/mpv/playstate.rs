@@ -6,14 +6,33 @@ use crate::util::{Percent, Positive};
 
 #[message_part]
 struct PlayState {
+    // Counts the states sent during the playback, so that a missed patch can be noticed
+    seq: u64,
     title: String,
     pause: bool,
     progress: Duration,
     length: Duration,
     volume: Option<Percent<Positive>>,
     chapter: Option<(i64, i64)>,
+    chapters: Vec<Chapter>,
     subtitles: Vec<Track>,
     audios: Vec<Track>,
+    tweaks: Tweaks,
+    // The part being played, if it can be skipped
+    skip: Option<Segment>,
+}
+
+#[message_part]
+enum Segment {
+    Intro,
+    Outro,
+}
+
+#[message_part]
+struct Chapter {
+    title: String,
+    // None if mpv doesn't know where the chapter starts
+    start: Option<Duration>,
 }
 
 #[message_part]
@@ -22,3 +41,206 @@ struct Track {
     title: String,
     selected: bool,
 }
+
+// The playback and video settings that are changed less often
+#[message_part]
+struct Tweaks {
+    // 100% is the normal speed
+    speed: Percent<Positive>,
+    // In milliseconds, positive means that the audio is played later
+    audio_delay: i64,
+    // Of the original size
+    zoom: Percent<Positive>,
+    // Of the video size, positive is to the right and down
+    pan: (i64, i64),
+    // The aspect ratio that is used instead of the video's own, such as "16:9"
+    aspect: Option<String>,
+    deinterlace: bool,
+    ab_loop: AbLoop,
+    loop_file: bool,
+}
+
+#[derive(Default)]
+#[message_part]
+enum AbLoop {
+    #[default]
+    Off,
+    // Only the start is set so far
+    A(Duration),
+    AB(Duration, Duration),
+}
+
+// The fields of a PlayState that changed since the previous state that was sent, the ones
+// that did not change are None. Only applies to the state with the seq right before it.
+#[derive(Default)]
+#[message_part]
+struct Patch {
+    seq: u64,
+    title: Option<String>,
+    pause: Option<bool>,
+    progress: Option<Duration>,
+    length: Option<Duration>,
+    volume: Option<Option<Percent<Positive>>>,
+    chapter: Option<Option<(i64, i64)>>,
+    chapters: Option<Vec<Chapter>>,
+    subtitles: Option<Vec<Track>>,
+    audios: Option<Vec<Track>>,
+    tweaks: Option<Tweaks>,
+    skip: Option<Option<Segment>>,
+}
+
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+impl PlayState {
+    /// What has to be applied to `self` to get `new`.
+    pub fn diff(&self, new: &PlayState) -> Patch {
+        Patch {
+            seq: new.seq,
+            title: changed(&self.title, &new.title),
+            pause: changed(&self.pause, &new.pause),
+            progress: changed(&self.progress, &new.progress),
+            length: changed(&self.length, &new.length),
+            volume: changed(&self.volume, &new.volume),
+            chapter: changed(&self.chapter, &new.chapter),
+            chapters: changed(&self.chapters, &new.chapters),
+            subtitles: changed(&self.subtitles, &new.subtitles),
+            audios: changed(&self.audios, &new.audios),
+            tweaks: changed(&self.tweaks, &new.tweaks),
+            skip: changed(&self.skip, &new.skip),
+        }
+    }
+
+    pub fn apply(&mut self, patch: Patch) {
+        let Patch {
+            seq,
+            title,
+            pause,
+            progress,
+            length,
+            volume,
+            chapter,
+            chapters,
+            subtitles,
+            audios,
+            tweaks,
+            skip,
+        } = patch;
+
+        fn set<T>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+
+        self.seq = seq;
+        set(&mut self.title, title);
+        set(&mut self.pause, pause);
+        set(&mut self.progress, progress);
+        set(&mut self.length, length);
+        set(&mut self.volume, volume);
+        set(&mut self.chapter, chapter);
+        set(&mut self.chapters, chapters);
+        set(&mut self.subtitles, subtitles);
+        set(&mut self.audios, audios);
+        set(&mut self.tweaks, tweaks);
+        set(&mut self.skip, skip);
+    }
+}
+
+impl Patch {
+    /// If nothing but the seq would change
+    pub fn is_empty(&self) -> bool {
+        *self
+            == Patch {
+                seq: self.seq,
+                ..Patch::default()
+            }
+    }
+}
+
+impl Default for Tweaks {
+    fn default() -> Self {
+        Self {
+            speed: Percent::new(100.0).expect("not NaN"),
+            audio_delay: 0,
+            zoom: Percent::new(100.0).expect("not NaN"),
+            pan: (0, 0),
+            aspect: None,
+            deinterlace: false,
+            ab_loop: AbLoop::Off,
+            loop_file: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playstate() -> PlayState {
+        PlayState {
+            seq: 1,
+            title: "title".to_string(),
+            pause: false,
+            progress: Duration::from_secs(10),
+            length: Duration::from_secs(100),
+            volume: Percent::new(50.0),
+            chapter: None,
+            chapters: Vec::new(),
+            subtitles: vec![Track {
+                id: 1,
+                title: "eng".to_string(),
+                selected: true,
+            }],
+            audios: Vec::new(),
+            tweaks: Tweaks::default(),
+            skip: None,
+        }
+    }
+
+    #[test]
+    fn progress_tick() {
+        let old = playstate();
+        let mut new = old.clone();
+        new.seq = 2;
+        new.progress = Duration::from_secs(11);
+
+        let patch = old.diff(&new);
+        assert_eq!(
+            Patch {
+                seq: 2,
+                progress: Some(Duration::from_secs(11)),
+                ..Patch::default()
+            },
+            patch
+        );
+
+        let mut applied = old;
+        applied.apply(patch);
+        assert_eq!(new, applied);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let old = playstate();
+        let mut new = old.clone();
+        new.seq = 2;
+        new.pause = true;
+        new.volume = None;
+        new.chapter = Some((1, 4));
+        new.subtitles[0].selected = false;
+        new.tweaks.ab_loop = AbLoop::A(Duration::from_secs(5));
+
+        let mut applied = old.clone();
+        applied.apply(old.diff(&new));
+        assert_eq!(new, applied);
+    }
+
+    #[test]
+    fn no_change() {
+        let state = playstate();
+        assert!(state.diff(&state).is_empty());
+    }
+}
@@ -0,0 +1,12 @@
+use protocol_macros::message_part;
+
+#[message_part]
+struct Queue {
+    entries: Vec<Entry>,
+}
+
+#[message_part]
+enum Entry {
+    File { root: usize, name: String },
+    Url { url: String },
+}
@@ -0,0 +1,10 @@
+use std::time::Duration;
+
+use protocol_macros::message_part;
+
+// Asks if a file should continue from where it was stopped the last time
+#[message_part]
+struct Resume {
+    name: String,
+    position: Duration,
+}
@@ -0,0 +1,77 @@
+use crate::to_client::ToClient;
+use protocol_macros::message_aggregator;
+
+// Images that are sent on the side, so they are not a part of any front
+#[message_aggregator(ToClient)]
+enum Preview {
+    Thumbnail(thumbnail::Thumbnail),
+    Sprites(sprites::Sprites),
+}
+
+pub mod thumbnail {
+    use crate::util::FileId;
+
+    #[protocol_macros::message_part]
+    struct Thumbnail {
+        id: FileId,
+        // None if the file has no picture, e.g., if it is only audio
+        jpeg: Option<Vec<u8>>,
+    }
+}
+
+pub mod sprites {
+    use std::time::Duration;
+
+    // Frames of the file being played, taken every `interval` and laid out left to right,
+    // top to bottom in a grid with `columns` columns.
+    #[protocol_macros::message_part]
+    struct Sprites {
+        interval: Duration,
+        columns: u32,
+        rows: u32,
+        count: u32,
+        jpeg: Vec<u8>,
+    }
+
+    impl Sprites {
+        /// The position of the tile showing `time`, as (column, row)
+        pub fn tile(&self, time: Duration) -> Option<(u32, u32)> {
+            if self.count == 0 || self.columns == 0 || self.interval.is_zero() {
+                return None;
+            }
+            let index = (time.as_secs_f64() / self.interval.as_secs_f64()) as u32;
+            let index = index.min(self.count - 1);
+            Some((index % self.columns, index / self.columns))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn sprites(count: u32) -> Sprites {
+            Sprites {
+                interval: Duration::from_secs(10),
+                columns: 4,
+                rows: count.div_ceil(4),
+                count,
+                jpeg: Vec::new(),
+            }
+        }
+
+        #[test]
+        fn tile_position() {
+            let s = sprites(10);
+            assert_eq!(Some((0, 0)), s.tile(Duration::ZERO));
+            assert_eq!(Some((0, 0)), s.tile(Duration::from_secs(9)));
+            assert_eq!(Some((1, 0)), s.tile(Duration::from_secs(10)));
+            assert_eq!(Some((1, 1)), s.tile(Duration::from_secs(55)));
+            assert_eq!(Some((1, 2)), s.tile(Duration::from_secs(1000)));
+        }
+
+        #[test]
+        fn no_tiles() {
+            assert_eq!(None, sprites(0).tile(Duration::from_secs(5)));
+        }
+    }
+}
@@ -5,6 +5,9 @@ pub mod mpvcontrol;
 pub mod mpvstart;
 pub mod playurlstart;
 pub mod powerctrl;
+pub mod previewreq;
+pub mod queuectrl;
+pub mod resumectrl;
 pub mod sendstatus;
 pub mod spotifyctrl;
 pub mod spotifystart;
@@ -21,4 +24,7 @@ enum ToServer {
     FsControl(fscontrol::FsControl),
     PlayUrlStart(playurlstart::PlayUrlStart),
     ErrorMsgCtrl(errormsgctrl::ErrorMsgCtrl),
+    QueueCtrl(queuectrl::QueueCtrl),
+    ResumeCtrl(resumectrl::ResumeCtrl),
+    PreviewReq(previewreq::PreviewReq),
 }
@@ -3,7 +3,29 @@ use crate::ToServer;
 pub mod search_ctrl {
     #[protocol_macros::message_aggregator]
     enum SearchCtrl {
-        Search(String),
+        Search(search::Search),
+    }
+
+    pub mod search {
+        #[protocol_macros::message_part]
+        struct Search {
+            query: String,
+            mode: SearchMode,
+        }
+
+        // How the query is matched against the files
+        #[derive(Default, Copy)]
+        #[protocol_macros::message_part]
+        enum SearchMode {
+            // The words in order, like swiper in Emacs
+            #[default]
+            Swiper,
+            // The characters of each word in order, like fzf
+            Fuzzy,
+            // Exactly the query
+            Literal,
+            Regex,
+        }
     }
 }
 
@@ -12,6 +34,16 @@ pub mod tree_ctrl {
     enum TreeCtrl {
         Cd(usize),
         CdDotDot,
+        Refresh,
+    }
+}
+
+pub mod watched_ctrl {
+    use crate::to_server::mpvstart;
+
+    #[protocol_macros::message_aggregator]
+    enum WatchedCtrl {
+        Toggle(mpvstart::file::File),
     }
 }
 
@@ -19,4 +51,5 @@ pub mod tree_ctrl {
 enum FsControl {
     SearchCtrl(search_ctrl::SearchCtrl),
     TreeCtrl(tree_ctrl::TreeCtrl),
+    WatchedCtrl(watched_ctrl::WatchedCtrl),
 }
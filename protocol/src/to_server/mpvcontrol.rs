@@ -12,6 +12,8 @@ enum MpvControl {
     SubDelayLater,
     NextChapter,
     PrevChapter,
+    // The index in PlayState's chapters
+    SetChapter(i64),
     SeekAbs(Percent<Normal>),
     SeekBack,
     SeekForward,
@@ -22,4 +24,24 @@ enum MpvControl {
     SubSmaller,
     SubMoveUp,
     SubMoveDown,
+    SpeedUp,
+    SpeedDown,
+    SpeedReset,
+    AudioDelayEarlier,
+    AudioDelayLater,
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    CycleAspect,
+    // Undo the zoom, pan and aspect changes
+    ResetVideo,
+    ToggleDeinterlace,
+    // Set the start of the loop, then the end, then stop looping
+    AbLoop,
+    ToggleLoopFile,
+    // Seek past the intro or outro that is being played
+    SkipSegment,
 }
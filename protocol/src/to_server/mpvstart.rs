@@ -9,21 +9,128 @@ enum MpvStart {
 }
 
 pub mod file {
+    use super::options::MpvOptions;
+    use crate::util::FileId;
+
     #[protocol_macros::message_part]
-    // TODO: It's risky to allow the client to play an arbitrary filepath. Each file
-    // option sent to the client should have some abstract id that represents it instead,
-    // like the root has in this case.
     struct File {
-        root: usize,
-        path: String,
+        id: FileId,
+        options: MpvOptions,
     }
 }
 
 pub mod url {
+    use super::options::MpvOptions;
+
     #[protocol_macros::message_part]
     struct Url {
         // TODO: use an URL type
         url: String,
+        quality: Quality,
+        options: MpvOptions,
+    }
+
+    // Which quality to play the URL in
+    #[protocol_macros::message_part]
+    enum Quality {
+        // Whatever the server is configured to use
+        Default,
+        // Video at most this many pixels high
+        MaxHeight(u32),
+        // Only the audio, no video
+        AudioOnly,
+    }
+}
+
+pub mod options {
+    use std::time::Duration;
+
+    use crate::util::{Percent, Positive};
+
+    // How to play a single file or URL. Anything not set is left to the server and mpv.
+    #[protocol_macros::message_part]
+    struct MpvOptions {
         paused: bool,
+        // Where to start, instead of asking whether to resume
+        start: Option<Duration>,
+        volume: Option<Percent<Positive>>,
+        // Subtitle files to load in addition to the embedded ones
+        sub_files: Vec<SubFile>,
+        // Play only the audio
+        audio_only: bool,
+        // Play the same thing over and over
+        loop_file: bool,
+        // Languages to choose before the configured ones, the most preferred first
+        sub_langs: Vec<String>,
+        audio_langs: Vec<String>,
+    }
+
+    impl MpvOptions {
+        pub fn new() -> Self {
+            Self {
+                paused: false,
+                start: None,
+                volume: None,
+                sub_files: Vec::new(),
+                audio_only: false,
+                loop_file: false,
+                sub_langs: Vec::new(),
+                audio_langs: Vec::new(),
+            }
+        }
+
+        pub fn paused(mut self, paused: bool) -> Self {
+            self.paused = paused;
+            self
+        }
+
+        pub fn start(mut self, start: Duration) -> Self {
+            self.start = Some(start);
+            self
+        }
+
+        pub fn volume(mut self, volume: Percent<Positive>) -> Self {
+            self.volume = Some(volume);
+            self
+        }
+
+        pub fn sub_file(mut self, sub_file: SubFile) -> Self {
+            self.sub_files.push(sub_file);
+            self
+        }
+
+        pub fn audio_only(mut self, audio_only: bool) -> Self {
+            self.audio_only = audio_only;
+            self
+        }
+
+        pub fn loop_file(mut self, loop_file: bool) -> Self {
+            self.loop_file = loop_file;
+            self
+        }
+
+        pub fn sub_langs(mut self, langs: Vec<String>) -> Self {
+            self.sub_langs = langs;
+            self
+        }
+
+        pub fn audio_langs(mut self, langs: Vec<String>) -> Self {
+            self.audio_langs = langs;
+            self
+        }
+    }
+
+    // Where a subtitle file is, the server doesn't load paths that it is given. The ones
+    // next to a video are found by the server itself.
+    #[protocol_macros::message_part]
+    enum SubFile {
+        // An http(s) URL to download it from
+        Url(String),
+    }
+
+    impl Default for MpvOptions {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 }
@@ -0,0 +1,9 @@
+use crate::util::FileId;
+
+#[protocol_macros::message_aggregator]
+enum PreviewReq {
+    // Thumbnails of these files, for the ones the client doesn't have yet
+    Thumbnails(Vec<FileId>),
+    // The sprites of what is currently playing
+    Sprites,
+}
@@ -0,0 +1,14 @@
+use crate::to_server::mpvstart;
+
+#[protocol_macros::message_aggregator]
+#[no_intos]
+enum QueueCtrl {
+    Show,
+    Hide,
+    AddFile(mpvstart::file::File),
+    AddUrl(mpvstart::url::Url),
+    Remove(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Skip,
+}
@@ -0,0 +1,5 @@
+#[protocol_macros::message_aggregator]
+enum ResumeCtrl {
+    Resume,
+    StartOver,
+}
@@ -117,6 +117,30 @@ impl<Kind: PercentKind> Default for Percent<Kind> {
     }
 }
 
+/// An opaque reference to a file on the server. It is only valid as long as the server's
+/// list of files stays the same, i.e., the same generation.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct FileId {
+    index: usize,
+    generation: u64,
+}
+
+impl FileId {
+    pub fn new(index: usize, generation: u64) -> Self {
+        Self { index, generation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
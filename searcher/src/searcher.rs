@@ -3,14 +3,17 @@ use std::collections::HashSet;
 use regex::Regex;
 
 use self::{
-    compile::{compile_search_term_to_regexes, Result},
+    compile::{compile_query, Highlight, Result},
     r#match::Match,
 };
 
+mod candidate;
 mod compile;
+mod filter;
 mod r#match;
 
-pub use compile::CompileError;
+pub use candidate::Candidate;
+pub use compile::{CompileError, ErrorKind, SearchMode};
 
 #[derive(Debug)]
 pub struct SearchRes<T> {
@@ -90,60 +93,78 @@ impl<T> SearchRes<T> {
     }
 }
 
-fn run_regexes_get_bytes(regs: &[Regex], string: &str) -> Option<HashSet<usize>> {
+fn run_regexes_get_bytes(
+    regs: &[Regex],
+    highlight: Highlight,
+    string: &str,
+) -> Option<HashSet<usize>> {
     assert!(!regs.is_empty());
     let mut bytes = HashSet::new();
     for reg in regs {
-        if let Some(caps) = reg.captures(string) {
-            caps.iter()
+        match highlight {
+            Highlight::Groups => reg
+                .captures(string)?
+                .iter()
                 .skip(1)
                 .flat_map(|mat| mat.expect("all capture groups should exist").range())
                 .for_each(|s| {
                     bytes.insert(s);
-                });
-        } else {
-            return None;
+                }),
+            // NOTE: the groups in a regex from the user might not take part in the match
+            Highlight::Whole => reg.find(string)?.range().for_each(|s| {
+                bytes.insert(s);
+            }),
         }
     }
     Some(bytes)
 }
 
-fn search_with_regex<'a, It, T>(regs: &[Regex], candidates: It) -> Vec<SearchRes<T>>
+fn search_with_regex<It, T>(
+    regs: &[Regex],
+    highlight: Highlight,
+    candidates: It,
+) -> Vec<SearchRes<T>>
 where
     T: AsRef<str>,
-    It: IntoIterator<Item = T>,
+    It: Iterator<Item = (usize, T)>,
 {
     candidates
-        .into_iter()
-        .enumerate()
         .filter_map(|(i, cand)| {
-            run_regexes_get_bytes(regs, cand.as_ref())
+            run_regexes_get_bytes(regs, highlight, cand.as_ref())
                 .map(|bytes| SearchRes::from_bytes(&bytes, i, cand))
         })
         .collect()
 }
 
-fn search_empty<'a, It, T>(candidates: It) -> Vec<SearchRes<T>>
+fn search_empty<It, T>(candidates: It) -> Vec<SearchRes<T>>
 where
-    It: IntoIterator<Item = T>,
+    It: Iterator<Item = (usize, T)>,
 {
     candidates
-        .into_iter()
-        .enumerate()
         .map(|(i, cand)| SearchRes::empty(i, cand))
         .collect()
 }
 
-pub fn search<'a, It, T>(search_term: &str, candidates: It) -> Result<Vec<SearchRes<T>>>
+pub fn search<'a, It, T>(
+    search_term: &str,
+    mode: SearchMode,
+    candidates: It,
+) -> Result<Vec<SearchRes<T>>>
 where
-    T: AsRef<str>,
+    T: Candidate,
     It: IntoIterator<Item = T>,
 {
-    if search_term.is_empty() {
+    let query = compile_query(search_term, mode)?;
+    let candidates = candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(_, cand)| query.keeps(cand));
+    if query.regexes.is_empty() {
         Ok(search_empty(candidates))
     } else {
         Ok(search_with_regex(
-            &compile_search_term_to_regexes(search_term)?,
+            &query.regexes,
+            query.highlight,
             candidates,
         ))
     }
@@ -167,12 +188,55 @@ mod test {
     fn test_search_simple() {
         let cands = vec!["hej"];
         assert_eq!(
-            search("hej", cands.clone()).unwrap(),
+            search("hej", SearchMode::Swiper, cands.clone()).unwrap(),
             vec![SearchRes::new(0, "hej", vec![0, 1, 2])]
         );
 
-        assert_eq!(search::<&[&'static str], _>("hej", &[]).unwrap(), vec![]);
+        assert_eq!(
+            search::<&[&'static str], _>("hej", SearchMode::Swiper, &[]).unwrap(),
+            vec![]
+        );
 
-        assert_eq!(search("nej", cands).unwrap(), vec![]);
+        assert_eq!(search("nej", SearchMode::Swiper, cands).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let cands = vec!["/a/hej.mkv", "/b/hej.mp4", "/a/nej.mkv"];
+        assert_eq!(
+            search("hej ext:mkv", SearchMode::Swiper, cands.clone()).unwrap(),
+            vec![SearchRes::new(0, "/a/hej.mkv", vec![3, 4, 5])]
+        );
+        assert_eq!(
+            search("-dir:a", SearchMode::Swiper, cands.clone()).unwrap(),
+            vec![SearchRes::new(1, "/b/hej.mp4", vec![])]
+        );
+        assert_eq!(
+            search("hej -mp4", SearchMode::Swiper, cands).unwrap(),
+            vec![SearchRes::new(0, "/a/hej.mkv", vec![3, 4, 5])]
+        );
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let cands = vec!["/a/Hej.mkv", "/b/hej-1.mp4"];
+        assert_eq!(
+            search("hj", SearchMode::Fuzzy, cands.clone()).unwrap(),
+            vec![
+                SearchRes::new(0, "/a/Hej.mkv", vec![3, 5]),
+                SearchRes::new(1, "/b/hej-1.mp4", vec![3, 5])
+            ]
+        );
+        assert_eq!(
+            search("Hej", SearchMode::Literal, cands.clone()).unwrap(),
+            vec![SearchRes::new(0, "/a/Hej.mkv", vec![3, 4, 5])]
+        );
+        assert_eq!(
+            search(r"j(-\d)?\.", SearchMode::Regex, cands).unwrap(),
+            vec![
+                SearchRes::new(0, "/a/Hej.mkv", vec![5, 6]),
+                SearchRes::new(1, "/b/hej-1.mp4", vec![5, 6, 7, 8])
+            ]
+        );
     }
 }
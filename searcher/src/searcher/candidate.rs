@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Something that can be searched for. The search term is matched against the string,
+/// which is usually a path, and the filters in the search term can also look at the rest.
+/// Everything that isn't known about the candidate doesn't match any filter, not even a
+/// negated one.
+pub trait Candidate: AsRef<str> {
+    /// The root directory the candidate is in
+    fn root(&self) -> Option<&str> {
+        None
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// If it has subtitles in the language called `lang`
+    fn has_subs(&self, _lang: &str) -> Option<bool> {
+        None
+    }
+
+    fn watched(&self) -> Option<bool> {
+        None
+    }
+}
+
+impl Candidate for str {}
+
+impl Candidate for String {}
+
+impl<T: Candidate + ?Sized> Candidate for &T {
+    fn root(&self) -> Option<&str> {
+        (**self).root()
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        (**self).duration()
+    }
+
+    fn has_subs(&self, lang: &str) -> Option<bool> {
+        (**self).has_subs(lang)
+    }
+
+    fn watched(&self) -> Option<bool> {
+        (**self).watched()
+    }
+}
@@ -1,7 +1,20 @@
+use std::{ops::Range, time::Duration};
+
 use regex::Regex;
 use regex_syntax::{escape, escape_into};
 
+use super::filter::{Filter, FilterKind};
+
 const QUOTE_WORD: &str = "'";
+const NEGATE: &str = "-";
+
+const FILTER_ROOT: &str = "root:";
+const FILTER_EXT: &str = "ext:";
+const FILTER_DIR: &str = "dir:";
+const FILTER_LONGER: &str = "dur>";
+const FILTER_SHORTER: &str = "dur<";
+const FILTER_SUBS: &str = "subs:";
+const FILTER_WATCHED: &str = "watched:";
 
 const REG_GROUP_START: &str = "(";
 const REG_GROUP_END: &str = ")";
@@ -9,30 +22,294 @@ const REG_ANY: &str = ".*?";
 const REG_ICASE: &str = "(?i)";
 const REG_NO_ICASE: &str = "(?-i)";
 
-// TODO: report what went wrong in more detail
-#[derive(Debug, thiserror::Error, PartialEq, Eq)]
-#[error("failed to compile query to regex")]
-pub struct CompileError;
+/// Why a search term could not be compiled, and where in it
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+#[error("{kind}")]
+pub struct CompileError {
+    pub kind: ErrorKind,
+    /// The characters in the search term that are wrong, empty if it isn't any part in
+    /// particular
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+pub enum ErrorKind {
+    #[error("there is nothing to search for")]
+    Empty,
+    #[error("the search term can not start with a space")]
+    LeadingSpace,
+    #[error("a quote must be followed by a word")]
+    EmptyQuote,
+    #[error("'{0}' must be followed by a value")]
+    MissingValue(&'static str),
+    #[error("'{0}' is not a number of minutes")]
+    NotMinutes(String),
+    #[error("'{0}' is not yes or no")]
+    NotYesNo(String),
+    #[error("invalid regex: {0}")]
+    InvalidRegex(String),
+}
 
 pub type Result<T> = std::result::Result<T, CompileError>;
 
-pub fn compile_search_term_to_regexes(search_term: &str) -> Result<Vec<Regex>> {
-    compile_swiper(search_term).map(|v| {
-        v.into_iter()
+impl CompileError {
+    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    /// The same error, but in a search term that has `by` more characters before it.
+    fn offset(self, by: usize) -> Self {
+        Self {
+            span: self.span.start + by..self.span.end + by,
+            ..self
+        }
+    }
+}
+
+/// How a search term is turned into what the candidates must match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// The words in order, separated by anything, like swiper in Emacs
+    #[default]
+    Swiper,
+    /// The characters of each word in order, in any order between the words, like fzf
+    Fuzzy,
+    /// Exactly the search term, case sensitive
+    Literal,
+    /// The search term is a regex
+    Regex,
+}
+
+/// What to highlight in a candidate that matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    /// The capture groups in the regexes
+    Groups,
+    /// Everything the regexes matched
+    Whole,
+}
+
+/// A compiled search term
+#[derive(Debug)]
+pub struct Query {
+    /// All of them must match the candidate, there are none if the search term only has
+    /// filters
+    pub regexes: Vec<Regex>,
+    pub filters: Vec<Filter>,
+    pub highlight: Highlight,
+}
+
+impl Query {
+    pub fn keeps<C: super::Candidate>(&self, cand: &C) -> bool {
+        self.filters.iter().all(|filter| filter.matches(cand))
+    }
+}
+
+/// Compile `search_term` in `mode`. Only swiper and fuzzy search terms can have filters,
+/// since the filters could just as well be part of a literal or a regex.
+pub fn compile_query(search_term: &str, mode: SearchMode) -> Result<Query> {
+    let (rest, filters) = match mode {
+        SearchMode::Swiper | SearchMode::Fuzzy => split_filters(search_term)?,
+        SearchMode::Literal | SearchMode::Regex => (Rest::all(search_term), Vec::new()),
+    };
+
+    let text = rest.text.as_str();
+    let regexes = match mode {
+        _ if text.is_empty() => Vec::new(),
+        SearchMode::Swiper => compile_swiper(text).map_err(|e| rest.in_term(e))?,
+        SearchMode::Fuzzy if text.trim().is_empty() => Vec::new(),
+        // NOTE: compiled from the whole search term, so that errors point to the right place
+        SearchMode::Fuzzy => compile_fzf(search_term)?,
+        SearchMode::Literal => vec![compile_literal(text)],
+        SearchMode::Regex => vec![compile_regex(text).map_err(|e| rest.in_term(e))?],
+    };
+    let highlight = match mode {
+        SearchMode::Regex => Highlight::Whole,
+        _ => Highlight::Groups,
+    };
+
+    Ok(Query {
+        regexes: regexes
+            .iter()
             .map(|reg_str| {
-                Regex::new(&reg_str).expect("the regex should always be correct")
+                Regex::new(reg_str).map_err(|e| rest.in_term(whole_term_error(text, e)))
             })
-            .collect()
+            .collect::<Result<_>>()?,
+        filters,
+        highlight,
     })
 }
 
-#[allow(dead_code)] // NOTE: maybe reintroduce in the future as a setting or something
+/// An error about all of `search_term`, for when the regex is wrong in some way that isn't
+/// about any part of it, e.g. it is too big.
+fn whole_term_error(search_term: &str, e: regex::Error) -> CompileError {
+    CompileError::new(
+        ErrorKind::InvalidRegex(e.to_string()),
+        0..search_term.chars().count(),
+    )
+}
+
+/// What is left of a search term when the filters are taken out
+#[derive(Debug, PartialEq, Eq)]
+struct Rest {
+    text: String,
+    /// Where each character of `text` is in the search term, and where the search term
+    /// ends
+    at: Vec<usize>,
+}
+
+impl Rest {
+    /// All of `search_term`
+    fn all(search_term: &str) -> Self {
+        Self::without(search_term, &vec![false; search_term.chars().count()])
+    }
+
+    /// `search_term` without the `removed` characters. They are removed together with the
+    /// spaces around them, and a single space is left between the words on either side.
+    fn without(search_term: &str, removed: &[bool]) -> Self {
+        let chars: Vec<char> = search_term.chars().collect();
+        let mut rest = Self {
+            text: String::new(),
+            at: Vec::new(),
+        };
+        let mut push = |c, at| {
+            rest.text.push(c);
+            rest.at.push(at);
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            let run_end = (i..chars.len())
+                .find(|&j| !removed[j] && chars[j] != ' ')
+                .unwrap_or(chars.len());
+            if !removed[i..run_end].contains(&true) {
+                push(chars[i], i);
+                i += 1;
+                continue;
+            }
+            // NOTE: a leading space is kept, since it is not allowed
+            if (i > 0 && run_end < chars.len()) || (i == 0 && chars[0] == ' ') {
+                push(' ', i);
+            }
+            i = run_end;
+        }
+        rest.at.push(chars.len());
+        rest
+    }
+
+    /// The same error, but with its span in the search term instead of in `text`.
+    fn in_term(&self, e: CompileError) -> CompileError {
+        let Range { start, end } = e.span;
+        let span = match end > start {
+            true => self.at[start]..self.at[end - 1] + 1,
+            false => self.at[start]..self.at[start],
+        };
+        CompileError { span, ..e }
+    }
+}
+
+/// Take the filters out of `search_term`, and leave the rest to be compiled into regexes.
+fn split_filters(search_term: &str) -> Result<(Rest, Vec<Filter>)> {
+    let mut filters = Vec::new();
+    let mut removed = vec![false; search_term.chars().count()];
+    for (at, word) in words(search_term) {
+        if let Some(filter) = compile_filter(word).map_err(|e| e.offset(at))? {
+            filters.push(filter);
+            removed[at..at + word.chars().count()].fill(true);
+        }
+    }
+    Ok((Rest::without(search_term, &removed), filters))
+}
+
+/// The words in `search_term` separated by single spaces, and the character index where
+/// each of them starts.
+fn words(search_term: &str) -> impl Iterator<Item = (usize, &str)> {
+    search_term.split(' ').scan(0, |at, word| {
+        let start = *at;
+        *at += word.chars().count() + 1;
+        Some((start, word))
+    })
+}
+
+/// The filter that `word` is, if it is one.
+fn compile_filter(word: &str) -> Result<Option<Filter>> {
+    let (negated, token) = match word.strip_prefix(NEGATE) {
+        Some(token) if !token.is_empty() => (true, token),
+        _ => (false, word),
+    };
+
+    let len = word.chars().count();
+    let value = |key: &'static str| {
+        token.strip_prefix(key).map(|value| match value.is_empty() {
+            true => Err(CompileError::new(ErrorKind::MissingValue(key), 0..len)),
+            false => Ok(value),
+        })
+    };
+    // NOTE: the values are always at the end of the word
+    let in_value = |value: &str| {
+        let at = len - value.chars().count();
+        move |e: CompileError| e.offset(at)
+    };
+
+    let kind = if let Some(root) = value(FILTER_ROOT) {
+        FilterKind::Root(root?.to_string())
+    } else if let Some(ext) = value(FILTER_EXT) {
+        FilterKind::Ext(ext?.trim_start_matches('.').to_string())
+    } else if let Some(dir) = value(FILTER_DIR) {
+        FilterKind::Dir(dir?.to_string())
+    } else if let Some(minutes) = value(FILTER_LONGER) {
+        let minutes = minutes?;
+        FilterKind::LongerThan(minutes_value(minutes).map_err(in_value(minutes))?)
+    } else if let Some(minutes) = value(FILTER_SHORTER) {
+        let minutes = minutes?;
+        FilterKind::ShorterThan(minutes_value(minutes).map_err(in_value(minutes))?)
+    } else if let Some(lang) = value(FILTER_SUBS) {
+        FilterKind::Subs(lang?.to_string())
+    } else if let Some(watched) = value(FILTER_WATCHED) {
+        let watched = watched?;
+        FilterKind::Watched(yes_no_value(watched).map_err(in_value(watched))?)
+    } else if negated {
+        FilterKind::Word(token.to_string())
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Filter { negated, kind }))
+}
+
+fn minutes_value(minutes: &str) -> Result<Duration> {
+    minutes
+        .parse::<u64>()
+        .map(|m| Duration::from_secs(m * 60))
+        .map_err(|_| {
+            CompileError::new(
+                ErrorKind::NotMinutes(minutes.to_string()),
+                0..minutes.chars().count(),
+            )
+        })
+}
+
+fn yes_no_value(value: &str) -> Result<bool> {
+    match value {
+        "yes" | "y" | "true" => Ok(true),
+        "no" | "n" | "false" => Ok(false),
+        _ => Err(CompileError::new(
+            ErrorKind::NotYesNo(value.to_string()),
+            0..value.chars().count(),
+        )),
+    }
+}
+
+/// Each word is its own regex. Filters are skipped, they are compiled by `split_filters`.
 fn compile_fzf(search_term: &str) -> Result<Vec<String>> {
-    let regs: Result<Vec<_>> = search_term.split_whitespace().map(compile_word).collect();
+    let regs: Result<Vec<_>> = words(search_term)
+        .filter(|(_, word)| !word.is_empty())
+        .filter(|(_, word)| !matches!(compile_filter(word), Ok(Some(_))))
+        .map(|(at, word)| compile_word(word).map_err(|e| e.offset(at)))
+        .collect();
 
     regs.and_then(|vec| {
         if vec.is_empty() {
-            Err(CompileError)
+            Err(CompileError::new(ErrorKind::Empty, 0..0))
         } else {
             Ok(vec)
         }
@@ -40,11 +317,14 @@ fn compile_fzf(search_term: &str) -> Result<Vec<String>> {
 }
 
 fn compile_swiper(mut search_term: &str) -> Result<Vec<String>> {
+    if search_term.is_empty() {
+        return Err(CompileError::new(ErrorKind::Empty, 0..0));
+    }
     if Regex::new(r"(^ ?$)|(^ [^ ])")
         .unwrap()
         .is_match(search_term)
     {
-        return Err(CompileError);
+        return Err(CompileError::new(ErrorKind::LeadingSpace, 0..1));
     }
 
     if Regex::new(r"[^ ] $").unwrap().is_match(search_term) {
@@ -75,11 +355,34 @@ fn compile_swiper(mut search_term: &str) -> Result<Vec<String>> {
     Ok(vec![regstring])
 }
 
+fn compile_literal(search_term: &str) -> String {
+    assert!(!search_term.is_empty());
+    String::new() + REG_NO_ICASE + REG_GROUP_START + &escape(search_term) + REG_GROUP_END
+}
+
+fn compile_regex(search_term: &str) -> Result<String> {
+    assert!(!search_term.is_empty());
+    // NOTE: parsed on its own first, since the error spans are then in the search term
+    if let Err(e) = regex_syntax::Parser::new().parse(search_term) {
+        let (message, span) = match &e {
+            regex_syntax::Error::Parse(e) => (e.kind().to_string(), Some(*e.span())),
+            regex_syntax::Error::Translate(e) => (e.kind().to_string(), Some(*e.span())),
+            _ => (e.to_string(), None),
+        };
+        let chars = |offset: usize| search_term[..offset].chars().count();
+        let span = span.map_or(0..chars(search_term.len()), |span| {
+            chars(span.start.offset)..chars(span.end.offset)
+        });
+        return Err(CompileError::new(ErrorKind::InvalidRegex(message), span));
+    }
+    Ok(String::new() + smart_case(search_term) + search_term)
+}
+
 fn compile_word(word: &str) -> Result<String> {
     assert!(!word.is_empty());
     let reg_str = if let Some(w) = word.strip_prefix(QUOTE_WORD) {
         if w.is_empty() {
-            return Err(CompileError);
+            return Err(CompileError::new(ErrorKind::EmptyQuote, 0..1));
         } else {
             literal_word(w)
         }
@@ -149,9 +452,13 @@ fn test_compile_fzf() {
 
 #[test]
 fn test_compile_swiper() {
-    assert_eq!(compile_swiper(" "), Err(CompileError));
-    assert_eq!(compile_swiper(""), Err(CompileError));
-    assert_eq!(compile_swiper(" x"), Err(CompileError));
+    let leading = CompileError::new(ErrorKind::LeadingSpace, 0..1);
+    assert_eq!(compile_swiper(" "), Err(leading.clone()));
+    assert_eq!(
+        compile_swiper(""),
+        Err(CompileError::new(ErrorKind::Empty, 0..0))
+    );
+    assert_eq!(compile_swiper(" x"), Err(leading));
     assert!(compile_swiper("x ").is_ok());
     assert!(compile_swiper("x  ").is_ok());
 
@@ -172,3 +479,142 @@ fn test_compile_swiper() {
         Ok(vec![format!("{}(hEj)", REG_NO_ICASE)])
     );
 }
+
+#[test]
+fn test_compile_filters() {
+    let (rest, filters) = split_filters("show ext:mkv  -dub root:Anime dur>20").unwrap();
+    assert_eq!(rest.text, "show");
+    assert_eq!(
+        filters,
+        vec![
+            Filter {
+                negated: false,
+                kind: FilterKind::Ext("mkv".into())
+            },
+            Filter {
+                negated: true,
+                kind: FilterKind::Word("dub".into())
+            },
+            Filter {
+                negated: false,
+                kind: FilterKind::Root("Anime".into())
+            },
+            Filter {
+                negated: false,
+                kind: FilterKind::LongerThan(Duration::from_secs(20 * 60))
+            },
+        ]
+    );
+
+    let rest = |search_term| split_filters(search_term).unwrap().0.text;
+    assert_eq!(rest("dir:x  y"), "y");
+    assert_eq!(rest(" dir:x y"), " y");
+    assert_eq!(rest("a ext:mkv  b"), "a b");
+    assert_eq!(rest("a  ext:mkv b"), "a b");
+    assert_eq!(rest("a  b ext:mkv -c"), "a  b");
+    assert_eq!(
+        split_filters("a - b: c").unwrap(),
+        (Rest::all("a - b: c"), vec![])
+    );
+    assert_eq!(
+        split_filters("-watched:no").unwrap().1,
+        vec![Filter {
+            negated: true,
+            kind: FilterKind::Watched(false)
+        }]
+    );
+
+    assert_eq!(
+        split_filters("x ext:").unwrap_err().kind,
+        ErrorKind::MissingValue(FILTER_EXT)
+    );
+    assert_eq!(
+        split_filters("dur<long").unwrap_err().kind,
+        ErrorKind::NotMinutes("long".into())
+    );
+    assert_eq!(
+        split_filters("watched:maybe").unwrap_err().kind,
+        ErrorKind::NotYesNo("maybe".into())
+    );
+
+    assert!(compile_query("", SearchMode::Swiper)
+        .unwrap()
+        .regexes
+        .is_empty());
+    assert!(compile_query("ext:mkv", SearchMode::Swiper)
+        .unwrap()
+        .regexes
+        .is_empty());
+    assert_eq!(
+        compile_query(" x", SearchMode::Swiper).unwrap_err().kind,
+        ErrorKind::LeadingSpace
+    );
+}
+
+#[test]
+fn test_error_spans() {
+    let span = |search_term| {
+        compile_query(search_term, SearchMode::Swiper)
+            .unwrap_err()
+            .span
+    };
+    assert_eq!(span(" x"), 0..1);
+    assert_eq!(span("x ext:"), 2..6);
+    assert_eq!(span("x -dir: y"), 2..7);
+    assert_eq!(span("dur<long"), 4..8);
+    assert_eq!(span("åäö  watched:kanske"), 13..19);
+
+    assert_eq!(compile_fzf("a  '").unwrap_err().span, 3..4);
+    assert_eq!(compile_fzf("  ").unwrap_err().span, 0..0);
+
+    let (rest, _) = split_filters("a ext:mkv  bc dir:x").unwrap();
+    assert_eq!(rest.text, "a bc");
+    let whole = CompileError::new(ErrorKind::Empty, 0..4);
+    assert_eq!(rest.in_term(whole).span, 0..13);
+    let word = CompileError::new(ErrorKind::Empty, 2..4);
+    assert_eq!(rest.in_term(word).span, 11..13);
+    let end = CompileError::new(ErrorKind::Empty, 4..4);
+    assert_eq!(rest.in_term(end).span, 19..19);
+}
+
+#[test]
+fn test_compile_modes() {
+    let regexes = |search_term, mode| -> Vec<String> {
+        compile_query(search_term, mode)
+            .unwrap()
+            .regexes
+            .iter()
+            .map(|reg| reg.as_str().to_string())
+            .collect()
+    };
+    assert_eq!(
+        regexes("a? dir:x", SearchMode::Fuzzy),
+        vec![format!("{REG_ICASE}{REG_ANY}(a){REG_ANY}(\\?){REG_ANY}")]
+    );
+    assert_eq!(
+        regexes("a? dir:x", SearchMode::Literal),
+        vec![format!("{REG_NO_ICASE}(a\\? dir:x)")]
+    );
+    assert_eq!(
+        regexes("a+ -b", SearchMode::Regex),
+        vec![format!("{REG_ICASE}a+ -b")]
+    );
+    assert!(regexes("ext:mkv  ", SearchMode::Fuzzy).is_empty());
+    for search_term in ["a  ext:mkv b", "a ext:mkv  b"] {
+        let query = compile_query(search_term, SearchMode::Swiper).unwrap();
+        assert!(query.regexes[0].is_match("/a x b.mkv"));
+    }
+    assert!(regexes("", SearchMode::Regex).is_empty());
+
+    let error = compile_query("ok(", SearchMode::Regex).unwrap_err();
+    assert!(matches!(error.kind, ErrorKind::InvalidRegex(_)));
+    assert_eq!(error.span, 2..3);
+    let error = compile_query("åä[b-a]", SearchMode::Regex).unwrap_err();
+    assert_eq!(error.span, 3..6);
+    assert_eq!(
+        compile_query("ext:mkv '", SearchMode::Fuzzy)
+            .unwrap_err()
+            .span,
+        8..9
+    );
+}
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use super::candidate::Candidate;
+
+/// A condition in the search term that the candidates must fulfill, other than matching
+/// the regexes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Filter {
+    pub negated: bool,
+    pub kind: FilterKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilterKind {
+    /// The string contains the word
+    Word(String),
+    Root(String),
+    /// The extension of the basename is exactly this, ignoring case
+    Ext(String),
+    /// Any of the directories above the basename contains this
+    Dir(String),
+    LongerThan(Duration),
+    ShorterThan(Duration),
+    Subs(String),
+    Watched(bool),
+}
+
+impl Filter {
+    pub fn matches<C: Candidate + ?Sized>(&self, cand: &C) -> bool {
+        let path = cand.as_ref();
+        let matches = match &self.kind {
+            FilterKind::Word(word) => Some(contains(path, word)),
+            FilterKind::Root(root) => cand.root().map(|r| contains(r, root)),
+            FilterKind::Ext(ext) => {
+                Some(extension(path).is_some_and(|e| e.eq_ignore_ascii_case(ext)))
+            }
+            FilterKind::Dir(dir) => Some(directories(path).any(|d| contains(d, dir))),
+            FilterKind::LongerThan(min) => cand.duration().map(|d| d > *min),
+            FilterKind::ShorterThan(max) => cand.duration().map(|d| d < *max),
+            FilterKind::Subs(lang) => cand.has_subs(lang),
+            FilterKind::Watched(watched) => cand.watched().map(|w| w == *watched),
+        };
+        // NOTE: what isn't known doesn't match, negated or not
+        matches.is_some_and(|matches| matches != self.negated)
+    }
+}
+
+/// If `haystack` contains `needle`, with smart case
+fn contains(haystack: &str, needle: &str) -> bool {
+    if needle.chars().any(|c| c.is_uppercase()) {
+        haystack.contains(needle)
+    } else {
+        haystack.to_lowercase().contains(needle)
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn extension(path: &str) -> Option<&str> {
+    basename(path)
+        .rsplit_once('.')
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(_, ext)| ext)
+}
+
+fn directories(path: &str) -> impl Iterator<Item = &str> {
+    path.rsplit_once('/')
+        .map_or("", |(dirs, _)| dirs)
+        .split('/')
+        .filter(|dir| !dir.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct File {
+        path: &'static str,
+        root: &'static str,
+        duration: Option<Duration>,
+    }
+
+    impl AsRef<str> for File {
+        fn as_ref(&self) -> &str {
+            self.path
+        }
+    }
+
+    impl Candidate for File {
+        fn root(&self) -> Option<&str> {
+            Some(self.root)
+        }
+
+        fn duration(&self) -> Option<Duration> {
+            self.duration
+        }
+    }
+
+    const FILE: File = File {
+        path: "/Anime/Show/Season 1/ep.01.MKV",
+        root: "/media/series",
+        duration: Some(Duration::from_secs(24 * 60)),
+    };
+
+    fn filter(negated: bool, kind: FilterKind) -> Filter {
+        Filter { negated, kind }
+    }
+
+    #[test]
+    fn path_filters() {
+        assert!(filter(false, FilterKind::Ext("mkv".into())).matches(&FILE));
+        assert!(!filter(false, FilterKind::Ext("01.mkv".into())).matches(&FILE));
+        assert!(filter(false, FilterKind::Dir("season".into())).matches(&FILE));
+        assert!(!filter(false, FilterKind::Dir("Season 2".into())).matches(&FILE));
+        assert!(!filter(false, FilterKind::Dir("ep".into())).matches(&FILE));
+        assert!(filter(false, FilterKind::Root("series".into())).matches(&FILE));
+        assert!(!filter(false, FilterKind::Root("Series".into())).matches(&FILE));
+        assert!(filter(true, FilterKind::Word("movie".into())).matches(&FILE));
+        assert!(!filter(true, FilterKind::Word("show".into())).matches(&FILE));
+    }
+
+    #[test]
+    fn unknown_metadata() {
+        let long = filter(false, FilterKind::LongerThan(Duration::from_secs(60)));
+        assert!(long.matches(&FILE));
+        assert!(!long.matches("/ep.01.mkv"));
+        assert!(!filter(false, FilterKind::Watched(false)).matches(&FILE));
+        assert!(!filter(true, FilterKind::Subs("en".into())).matches(&FILE));
+        let short = filter(true, FilterKind::LongerThan(Duration::from_secs(60)));
+        assert!(!short.matches(&FILE));
+        assert!(!short.matches("/ep.01.mkv"));
+        assert!(!filter(true, FilterKind::Root("series".into())).matches("/ep.01.mkv"));
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(Some("mkv"), extension("/a.b/c.mkv"));
+        assert_eq!(None, extension("/a.b/c"));
+        assert_eq!(None, extension("/.hidden"));
+        assert_eq!(
+            vec!["a.b", "c"],
+            directories("/a.b/c/d.mkv").collect::<Vec<_>>()
+        );
+        assert_eq!(0, directories("/d.mkv").count());
+    }
+}
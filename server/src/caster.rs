@@ -1,17 +1,85 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use protocol::to_client::{
+    front::{mpv::Mpv, Front},
+    ToClient,
+};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
-use crate::state_machine;
+use crate::{filer::shared::SharedCache, mpv::thumbnail, state_machine};
+
+pub type Sender = mpsc::Sender<ToConn>;
+pub type Receiver = mpsc::Receiver<(ClientId, protocol::ToServer)>;
+
+/// Tells the connected clients apart, so that an answer can go to the one that asked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+impl ClientId {
+    /// An id that no other client has had
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A message for the connections to send
+#[derive(Debug)]
+pub enum ToConn {
+    /// For every client, fronts are remembered for the clients that connect later
+    All(ToClient),
+    /// Only for one client, like a picture it asked for
+    One(ClientId, ToClient),
+}
 
-pub type Sender = mpsc::Sender<protocol::ToClient>;
-pub type Receiver = mpsc::Receiver<protocol::ToServer>;
+/// Remembers the last state sent to the clients. Patches are applied to it, so it is
+/// always a full state that can be sent to a client that has nothing.
+pub struct Gatekeeper {
+    last_sent: Front,
+}
+
+impl Gatekeeper {
+    pub fn new(initial_state: Front) -> Self {
+        Self {
+            last_sent: initial_state,
+        }
+    }
+
+    pub fn last_sent(&self) -> Front {
+        self.last_sent.clone()
+    }
+
+    /// If the clients have a full `PlayState` that patches can be applied to
+    pub fn has_playstate(&self) -> bool {
+        matches!(self.last_sent, Front::Mpv(Mpv::PlayState(_)))
+    }
+
+    pub fn set_last_sent(&mut self, msg: &ToClient) {
+        match (msg, &mut self.last_sent) {
+            (
+                ToClient::Front(Front::Mpv(Mpv::PlayStatePatch(patch))),
+                Front::Mpv(Mpv::PlayState(state)),
+            ) => state.apply(patch.clone()),
+            (ToClient::Front(Front::Mpv(Mpv::PlayStatePatch(_))), _) => {
+                log::warn!("A patch was sent without a PlayState to apply it to")
+            }
+            (ToClient::Front(f), _) => self.last_sent = f.clone(),
+            _ => (),
+        }
+    }
+}
 
 pub async fn caster_actor(
     to_conn: Sender,
     from_conn: Receiver,
+    thumbnailer: thumbnail::Sender,
+    cache: SharedCache,
     canceltoken: CancellationToken,
 ) -> anyhow::Result<()> {
-    let ret = state_machine::state_start(from_conn, to_conn, canceltoken).await;
+    let ret =
+        state_machine::state_start(from_conn, to_conn, thumbnailer, cache, canceltoken)
+            .await;
     log::info!("Caster actor exited");
     ret
 }
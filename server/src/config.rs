@@ -1,4 +1,4 @@
-use std::{fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
 
 use anyhow::Context;
 use tokio::sync::OnceCell;
@@ -15,11 +15,135 @@ static CONF: OnceCell<Config> = OnceCell::const_new();
 struct Config {
     root_dirs: Vec<String>,
     port: u16,
+    max_clients: Option<usize>,
     poweroff_exe: String,
     refresh_cache_boot: bool,
+    watch_files: bool,
+    probe_media: bool,
+    filter: Filters,
+    mpv: Mpv,
     spotify: Spotify,
 }
 
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Filters {
+    extensions: Vec<String>,
+    ignore: Vec<String>,
+    min_size: u64,
+    #[serde(default)]
+    roots: HashMap<String, FilterOverride>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilterOverride {
+    extensions: Option<Vec<String>>,
+    ignore: Option<Vec<String>>,
+    min_size: Option<u64>,
+}
+
+/// Which files in a root to include in the cache
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Filter {
+    /// File extensions, without the dot, of the files to include
+    pub extensions: Vec<String>,
+    /// Glob patterns of files to leave out, matched against the path relative to the root
+    pub ignore: Vec<String>,
+    /// Files smaller than this many bytes are left out
+    pub min_size: u64,
+}
+
+impl Filter {
+    pub fn ignore_set(&self) -> Result<globset::GlobSet, globset::Error> {
+        let mut builder = globset::GlobSetBuilder::new();
+        for pattern in &self.ignore {
+            builder.add(globset::Glob::new(pattern)?);
+        }
+        builder.build()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Mpv {
+    watched_threshold: f64,
+    languages: LanguagesConf,
+    ytdl: YtdlPolicy,
+    updates: Updates,
+    skip: SkipConf,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Updates {
+    max_per_sec: u32,
+    batch_ms: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguagesConf {
+    sub: TrackPrefs,
+    audio: TrackPrefs,
+    #[serde(default)]
+    roots: HashMap<String, LanguagesOverride>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguagesOverride {
+    sub: Option<TrackPrefs>,
+    audio: Option<TrackPrefs>,
+}
+
+/// Which tracks of one type to choose automatically
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackPrefs {
+    /// Languages to choose, the most preferred first
+    pub langs: Vec<String>,
+    /// Title keywords of tracks to choose before others in the same language, the most
+    /// preferred first
+    pub prefer_titles: Vec<String>,
+    /// Title keywords of tracks to only choose if there is nothing better
+    pub avoid_titles: Vec<String>,
+}
+
+/// Which format to play videos from URLs in
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct YtdlPolicy {
+    /// The highest resolution to play videos in, unlimited if not set
+    pub max_height: Option<u32>,
+    /// Video codecs to prefer, the most preferred first
+    pub codecs: Vec<String>,
+    /// Play only the audio
+    pub audio_only: bool,
+}
+
+/// Which parts of the videos can be skipped
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkipConf {
+    /// Title keywords of the chapters that are intros
+    pub intro_chapters: Vec<String>,
+    /// Title keywords of the chapters that are outros
+    pub outro_chapters: Vec<String>,
+    /// Name of the file, in the same directory as the videos, with the times of their
+    /// intro and outro, either for all of them or by file name
+    pub marker_file: String,
+    /// Root dirs where the intros and outros are skipped without asking
+    pub auto_roots: Vec<String>,
+}
+
+/// Which tracks to choose automatically for the files in a root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Languages {
+    pub sub: TrackPrefs,
+    pub audio: TrackPrefs,
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct Spotify {
@@ -33,11 +157,88 @@ pub fn init_config() -> anyhow::Result<()> {
         .with_context(|| format!("reading config file at {:?}", conf_file))?;
 
     let conf: Config = toml::from_str(&conts).context("parsing config file as TOML")?;
+    conf.validate()?;
 
     CONF.set(conf).context("setting the global conf variable")?;
     Ok(())
 }
 
+impl Config {
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(root) = self
+            .filter
+            .roots
+            .keys()
+            .find(|root| !self.root_dirs.contains(root))
+        {
+            anyhow::bail!("there is a filter for '{root}', but it is not a root dir");
+        }
+
+        if let Some(root) = self
+            .mpv
+            .languages
+            .roots
+            .keys()
+            .find(|root| !self.root_dirs.contains(root))
+        {
+            anyhow::bail!("there are languages for '{root}', but it is not a root dir");
+        }
+
+        if let Some(root) = self
+            .mpv
+            .skip
+            .auto_roots
+            .iter()
+            .find(|root| !self.root_dirs.contains(root))
+        {
+            anyhow::bail!("'{root}' is set to auto skip, but it is not a root dir");
+        }
+
+        if self.mpv.updates.max_per_sec == 0 {
+            anyhow::bail!("mpv.updates.max_per_sec must be above zero");
+        }
+
+        for filter in self.filters() {
+            filter.ignore_set().context("parsing the ignore patterns")?;
+        }
+
+        Ok(())
+    }
+
+    fn filters(&self) -> Vec<Filter> {
+        self.root_dirs
+            .iter()
+            .map(|root| {
+                let over = self.filter.roots.get(root);
+                Filter {
+                    extensions: over
+                        .and_then(|o| o.extensions.clone())
+                        .unwrap_or_else(|| self.filter.extensions.clone()),
+                    ignore: over
+                        .and_then(|o| o.ignore.clone())
+                        .unwrap_or_else(|| self.filter.ignore.clone()),
+                    min_size: over
+                        .and_then(|o| o.min_size)
+                        .unwrap_or(self.filter.min_size),
+                }
+            })
+            .collect()
+    }
+
+    fn languages(&self, root: Option<&str>) -> Languages {
+        let langs = &self.mpv.languages;
+        let over = root.and_then(|root| langs.roots.get(root));
+        Languages {
+            sub: over
+                .and_then(|o| o.sub.clone())
+                .unwrap_or_else(|| langs.sub.clone()),
+            audio: over
+                .and_then(|o| o.audio.clone())
+                .unwrap_or_else(|| langs.audio.clone()),
+        }
+    }
+}
+
 fn get_instance() -> &'static Config {
     CONF.get().expect("Config was not initialized")
 }
@@ -46,10 +247,19 @@ pub fn root_dirs() -> &'static [String] {
     &get_instance().root_dirs
 }
 
+/// The filter of every root dir, in the same order as [`root_dirs`]
+pub fn filters() -> Vec<Filter> {
+    get_instance().filters()
+}
+
 pub fn port() -> u16 {
     get_instance().port
 }
 
+pub fn max_clients() -> Option<usize> {
+    get_instance().max_clients
+}
+
 pub fn spotify_exe() -> &'static str {
     &get_instance().spotify.executable
 }
@@ -66,6 +276,46 @@ pub fn refresh_cache_boot() -> bool {
     get_instance().refresh_cache_boot
 }
 
+pub fn watch_files() -> bool {
+    get_instance().watch_files
+}
+
+pub fn probe_media() -> bool {
+    get_instance().probe_media
+}
+
+pub fn watched_threshold() -> f64 {
+    get_instance().mpv.watched_threshold
+}
+
+/// The languages to use for files in `root`, or for things not in any root if `None`
+pub fn languages(root: Option<&str>) -> Languages {
+    get_instance().languages(root)
+}
+
+/// The shortest time between two updates of the playback state to the clients
+pub fn update_interval() -> Duration {
+    Duration::from_secs(1) / get_instance().mpv.updates.max_per_sec
+}
+
+/// How long to gather changes to the playback state before sending them
+pub fn update_batch() -> Duration {
+    Duration::from_millis(get_instance().mpv.updates.batch_ms)
+}
+
+pub fn ytdl_policy() -> &'static YtdlPolicy {
+    &get_instance().mpv.ytdl
+}
+
+pub fn skip_conf() -> &'static SkipConf {
+    &get_instance().mpv.skip
+}
+
+/// If the intros and outros of the files in `root` should be skipped without asking
+pub fn auto_skip(root: Option<&str>) -> bool {
+    root.is_some_and(|root| skip_conf().auto_roots.iter().any(|r| r == root))
+}
+
 // TODO: make configurable
 pub fn mpv_conf_dir() -> PathBuf {
     conf_dir().join("mpv")
@@ -93,5 +343,126 @@ mod test {
         let conf: Result<Config, _> = toml::from_str(&conts);
         println!("conf: {conf:#?}");
         assert!(conf.is_ok());
+        assert!(conf.unwrap().validate().is_ok());
+    }
+
+    #[test]
+    fn filter_overrides() {
+        let conf: Config = toml::from_str(
+            r#"
+            root_dirs = ["/a", "/b"]
+            port = 1
+            poweroff_exe = ""
+            refresh_cache_boot = false
+            watch_files = false
+            probe_media = false
+            [filter]
+            extensions = ["mkv"]
+            ignore = ["*.part"]
+            min_size = 10
+            [filter.roots."/b"]
+            extensions = ["mp4", "webm"]
+            [mpv]
+            watched_threshold = 90.0
+            [mpv.languages.sub]
+            langs = ["en"]
+            prefer_titles = []
+            avoid_titles = []
+            [mpv.languages.audio]
+            langs = ["en"]
+            prefer_titles = []
+            avoid_titles = []
+            [mpv.ytdl]
+            codecs = []
+            audio_only = false
+            [mpv.updates]
+            max_per_sec = 4
+            batch_ms = 50
+            [mpv.skip]
+            intro_chapters = []
+            outro_chapters = []
+            marker_file = ".skip.toml"
+            auto_roots = []
+            [spotify]
+            executable = ""
+            fullscreen_exe = ""
+            "#,
+        )
+        .unwrap();
+        assert!(conf.validate().is_ok());
+
+        let filters = conf.filters();
+        assert_eq!(filters[0].extensions, vec!["mkv"]);
+        assert_eq!(filters[1].extensions, vec!["mp4", "webm"]);
+        assert_eq!(filters[1].ignore, vec!["*.part"]);
+        assert_eq!(filters[1].min_size, 10);
+    }
+
+    #[test]
+    fn language_overrides() {
+        let conf: Config = toml::from_str(
+            r#"
+            root_dirs = ["/anime", "/docs"]
+            port = 1
+            poweroff_exe = ""
+            refresh_cache_boot = false
+            watch_files = false
+            probe_media = false
+            [filter]
+            extensions = ["mkv"]
+            ignore = []
+            min_size = 0
+            [mpv]
+            watched_threshold = 90.0
+            [mpv.languages.sub]
+            langs = ["en"]
+            prefer_titles = ["dialogue"]
+            avoid_titles = ["signs"]
+            [mpv.languages.audio]
+            langs = ["ja", "en"]
+            prefer_titles = []
+            avoid_titles = []
+            [mpv.languages.roots."/docs".audio]
+            langs = ["en"]
+            prefer_titles = []
+            avoid_titles = ["commentary"]
+            [mpv.ytdl]
+            codecs = []
+            audio_only = false
+            [mpv.updates]
+            max_per_sec = 4
+            batch_ms = 50
+            [mpv.skip]
+            intro_chapters = []
+            outro_chapters = []
+            marker_file = ".skip.toml"
+            auto_roots = []
+            [spotify]
+            executable = ""
+            fullscreen_exe = ""
+            "#,
+        )
+        .unwrap();
+        assert!(conf.validate().is_ok());
+
+        let anime = conf.languages(Some("/anime"));
+        assert_eq!(anime.audio.langs, vec!["ja", "en"]);
+        assert_eq!(anime, conf.languages(None));
+
+        let docs = conf.languages(Some("/docs"));
+        assert_eq!(docs.audio.langs, vec!["en"]);
+        assert_eq!(docs.audio.avoid_titles, vec!["commentary"]);
+        assert_eq!(docs.sub, anime.sub);
+    }
+
+    #[test]
+    fn auto_skip_roots() {
+        let mut conf: Config =
+            toml::from_str(include_str!("../config.def.toml")).unwrap();
+        conf.mpv.skip.auto_roots = vec!["/anime".to_string()];
+        assert!(conf.validate().is_err());
+
+        conf.root_dirs.push("/anime".to_string());
+        assert!(conf.validate().is_ok());
     }
 }
@@ -2,22 +2,39 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 use anyhow::Context;
 use futures_util::{Sink, SinkExt, StreamExt, TryStreamExt};
-use protocol::{to_client::seat::Seat, Message, ToClientable};
+use protocol::{
+    to_client::{front::Front, seat::Seat},
+    Message, ToClientable,
+};
 use tokio::{
     net::{TcpListener, TcpStream},
     select,
-    sync::mpsc,
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinSet,
 };
 use tokio_tungstenite::tungstenite::Message as TungMsg;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
+    caster::{ClientId, Gatekeeper, ToConn},
     config,
-    util::{join_handle_wait_take, FutureCancel},
+    util::{join_handle_unwrap, FutureCancel},
 };
 
-type Sender = mpsc::Sender<protocol::ToServer>;
-type Receiver = mpsc::Receiver<protocol::ToClient>;
+const CLIENT_CHANNEL_SIZE: usize = 1024;
+
+type Sender = mpsc::Sender<(ClientId, protocol::ToServer)>;
+type Receiver = mpsc::Receiver<ToConn>;
+
+/// A connected client, messages sent here are forwarded to its websocket
+struct Client {
+    id: ClientId,
+    addr: SocketAddr,
+    to_client: mpsc::Sender<protocol::ToClient>,
+    /// If messages were dropped since its channel was full, it gets the full state once
+    /// there is room again
+    lagging: bool,
+}
 
 async fn ws_send<T, S>(msg: T, ws: &mut S) -> anyhow::Result<()>
 where
@@ -39,49 +56,13 @@ async fn reject(tcp_stream: TcpStream) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_rejections(
-    listener: TcpListener,
-    canceltoken: CancellationToken,
-) -> anyhow::Result<TcpListener> {
-    log::info!("Rejecting new connections");
-    loop {
-        log::debug!("Waiting for new connection to reject...");
-        let (stream, addr) = match listener.accept().cancellable(&canceltoken).await {
-            Some(x) => x.context("failed to accept tcp stream")?,
-            None => {
-                log::debug!("Handle_rejections got cancelled");
-                return Ok(listener);
-            }
-        };
-
-        log::info!("Rejecting {}...", addr);
-        if let Err(e) = reject(stream).await {
-            log::warn!("Did not reject {} successfully cuz {:?}", addr, e);
-        } else {
-            log::info!("Rejected {}", addr);
-        }
-    }
-}
-
-async fn throw_away(mut from_cast: Receiver, canceltoken: CancellationToken) -> Receiver {
-    log::debug!("Throwing away messages from caster");
-    loop {
-        match from_cast.recv().cancellable(&canceltoken).await {
-            None => break from_cast,
-            Some(Some(msg)) => log::trace!("Threw away msg: {:?}", msg),
-            Some(None) => {
-                log::warn!("Caster seems to be down");
-                break from_cast;
-            }
-        };
-    }
-}
-
 async fn handle_accept(
     tcp_stream: TcpStream,
+    id: ClientId,
     addr: SocketAddr,
-    to_cast: &mut Sender,
-    from_cast: &mut Receiver,
+    mut to_cast: Sender,
+    mut from_cast: mpsc::Receiver<protocol::ToClient>,
+    last_sent: Front,
     canceltoken: CancellationToken,
 ) -> anyhow::Result<()> {
     log::info!("Accepting connection from: {}", addr);
@@ -90,6 +71,7 @@ async fn handle_accept(
 
     log::debug!("Sending accept...");
     ws_send(Seat::Accept, &mut sink).await?;
+    ws_send(last_sent, &mut sink).await?;
 
     loop {
         select! {
@@ -99,7 +81,7 @@ async fn handle_accept(
                         log::info!("Client closed");
                         break;
                     },
-                    Some(TungMsg::Binary(msg)) => handle_binary(&msg, to_cast).await,
+                    Some(TungMsg::Binary(msg)) => handle_binary(&msg, id, &mut to_cast).await,
                     Some(TungMsg::Close(msg)) => log::debug!("Closed message with '{:?}'", msg),
                     Some(msg) => log::warn!("Got a non-binary message {:?}", msg),
                 }
@@ -125,12 +107,16 @@ async fn handle_accept(
     Ok(())
 }
 
-async fn handle_binary(msg: &[u8], to_cast: &mut Sender) {
+async fn handle_binary(msg: &[u8], id: ClientId, to_cast: &mut Sender) {
     match Message::deserialize(msg) {
         Err(e) => log::warn!("Failed to deserialize message {:?} cuz {}", &msg, e),
         Ok(m) if m.is_to_server() => {
             log::trace!("Received: {m:?}");
-            if to_cast.send(m.take_to_server().unwrap()).await.is_err() {
+            if to_cast
+                .send((id, m.take_to_server().unwrap()))
+                .await
+                .is_err()
+            {
                 log::warn!("Seems like caster is down");
             }
         }
@@ -138,52 +124,155 @@ async fn handle_binary(msg: &[u8], to_cast: &mut Sender) {
     }
 }
 
+/// Send `msg` to every client without waiting for the slow ones, forgetting the ones that
+/// have disconnected. A client whose channel is full misses messages until it can be sent
+/// the full state in `keeper` instead.
+fn fan_out(clients: &mut Vec<Client>, msg: protocol::ToClient, keeper: &Gatekeeper) {
+    let mut disconnected = Vec::new();
+    for (i, client) in clients.iter_mut().enumerate() {
+        let res = if client.lagging {
+            client.to_client.try_send(keeper.last_sent().to_client())
+        } else {
+            client.to_client.try_send(msg.clone())
+        };
+        match res {
+            Ok(()) => {
+                if client.lagging {
+                    log::info!("Resynced lagging client: {}", client.addr);
+                }
+                client.lagging = false;
+            }
+            Err(TrySendError::Full(_)) => {
+                if !client.lagging {
+                    log::warn!("Client {} is lagging, dropping messages", client.addr);
+                }
+                client.lagging = true;
+            }
+            Err(TrySendError::Closed(_)) => disconnected.push(i),
+        }
+    }
+
+    for i in disconnected.into_iter().rev() {
+        let client = clients.swap_remove(i);
+        log::debug!("Forgetting disconnected client: {}", client.addr);
+    }
+}
+
+/// Send `msg` to the client `id` only. It is dropped if the client can't keep up, since
+/// the client asks again for what it still wants.
+fn send_one(clients: &[Client], id: ClientId, msg: protocol::ToClient) {
+    let Some(client) = clients.iter().find(|c| c.id == id) else {
+        log::debug!("Not sending to {id:?}, it has disconnected");
+        return;
+    };
+    if let Err(TrySendError::Full(_)) = client.to_client.try_send(msg) {
+        log::warn!(
+            "Client {} is lagging, dropping a message to it",
+            client.addr
+        );
+    }
+}
+
+fn handle_new_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    clients: &mut Vec<Client>,
+    tasks: &mut JoinSet<()>,
+    to_cast: &Sender,
+    last_sent: Front,
+    canceltoken: CancellationToken,
+) {
+    clients.retain(|c| !c.to_client.is_closed());
+
+    if config::max_clients().is_some_and(|max| clients.len() >= max) {
+        log::info!(
+            "Rejecting {}, {} clients already connected",
+            addr,
+            clients.len()
+        );
+        tasks.spawn(async move {
+            match reject(stream).cancellable(&canceltoken).await {
+                None => log::debug!("Rejecting {} got cancelled", addr),
+                Some(Err(e)) => {
+                    log::warn!("Did not reject {} successfully cuz {:?}", addr, e)
+                }
+                Some(Ok(())) => log::info!("Rejected {}", addr),
+            }
+        });
+        return;
+    }
+
+    let id = ClientId::unique();
+    let (to_client, from_cast) = mpsc::channel(CLIENT_CHANNEL_SIZE);
+    clients.push(Client {
+        id,
+        addr,
+        to_client,
+        lagging: false,
+    });
+    let to_cast = to_cast.clone();
+    tasks.spawn(async move {
+        if let Err(e) =
+            handle_accept(stream, id, addr, to_cast, from_cast, last_sent, canceltoken)
+                .await
+        {
+            log::warn!("Handle_accept for {} exited with an error: {:?}", addr, e);
+        }
+    });
+}
+
 pub async fn connections_actor(
-    mut to_cast: Sender,
+    to_cast: Sender,
     mut from_cast: Receiver,
     canceltoken: CancellationToken,
 ) -> anyhow::Result<()> {
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), config::port());
-    let mut listener = TcpListener::bind(addr).await?;
+    let listener = TcpListener::bind(addr).await?;
     log::info!("Listening on: {}", addr);
 
+    let mut clients = Vec::new();
+    let mut tasks = JoinSet::new();
+    let mut keeper = Gatekeeper::new(Front::None);
+
     loop {
-        let throw_token = canceltoken.child_token();
-        let throw_handle = tokio::spawn(throw_away(from_cast, throw_token.clone()));
-
-        log::debug!("Waiting for a new connection to accept...");
-        let (stream, addr) = match listener.accept().cancellable(&canceltoken).await {
-            Some(x) => x.context("failed to accept tcp stream")?,
-            None => {
-                log::debug!("Connections is aborting at accept with no one connected...");
+        select! {
+            _ = canceltoken.cancelled() => {
+                log::debug!("Connections is aborting...");
                 break;
-            }
-        };
-
-        log::debug!("Cancelling and waiting for throw_handle to exit...");
-        throw_token.cancel();
-        from_cast = join_handle_wait_take(throw_handle).await;
-
-        let rejections_token = canceltoken.child_token();
-        let rej_handle =
-            tokio::spawn(handle_rejections(listener, rejections_token.clone()));
-        if let Err(e) = handle_accept(
-            stream,
-            addr,
-            &mut to_cast,
-            &mut from_cast,
-            canceltoken.child_token(),
-        )
-        .await
-        {
-            log::warn!("Handle_accept exited with an error: {:?}", e);
+            },
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted.context("failed to accept tcp stream")?;
+                handle_new_connection(
+                    stream,
+                    addr,
+                    &mut clients,
+                    &mut tasks,
+                    &to_cast,
+                    keeper.last_sent(),
+                    canceltoken.child_token(),
+                );
+            },
+            msg = from_cast.recv() => {
+                let Some(msg) = msg else {
+                    log::warn!("Caster seems to be down");
+                    break;
+                };
+                match msg {
+                    ToConn::All(msg) => {
+                        keeper.set_last_sent(&msg);
+                        fan_out(&mut clients, msg, &keeper);
+                    }
+                    ToConn::One(id, msg) => send_one(&clients, id, msg),
+                }
+            },
+            Some(res) = tasks.join_next() => join_handle_unwrap(res),
         }
+    }
 
-        log::debug!("Cancelling and waiting for handle_rejections to exit...");
-        rejections_token.cancel();
-        listener = join_handle_wait_take(rej_handle)
-            .await
-            .context("handle_rejections failed to give back TcpListener")?;
+    log::debug!("Waiting for {} connection tasks to exit...", tasks.len());
+    drop(clients);
+    while let Some(res) = tasks.join_next().await {
+        join_handle_unwrap(res);
     }
 
     log::info!("Connections actor exited");
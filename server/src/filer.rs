@@ -1,6 +1,9 @@
 pub mod cache;
+pub mod prober;
 pub mod search;
+pub mod shared;
 pub mod tree;
+pub mod watcher;
 
 use std::{
     io,
@@ -20,35 +23,63 @@ pub enum FilerError {
     IoError(#[from] io::Error),
     #[error("Failed to write to the cache cuz: {0:?}")]
     Bincode(#[from] bincode::Error),
+    #[error("Invalid ignore pattern: {0}")]
+    Glob(#[from] globset::Error),
+    #[error("The file list has changed since the file was shown")]
+    OldFileId,
+    #[error("Could not find {0} in the cache")]
+    NotInCache(String),
+    #[error("The cache was saved by another version, {0}")]
+    CacheVersion(u32),
 }
 
 pub fn cache_file() -> PathBuf {
     config::cache_dir().join("files_cache")
 }
 
-pub async fn refresh_cache<F, Fut>(prog_report: F) -> FilerResult<Cache>
+/// Scan all roots into a new cache. What is known to be in the files that were already in
+/// `old` is kept.
+pub async fn refresh_cache<F, Fut>(old: &Cache, prog_report: F) -> FilerResult<Cache>
 where
     F: FnMut(filesearch::refreshing::Refreshing) -> Fut,
     Fut: Future<Output = ()>,
 {
     log::info!("Refreshing cache");
-    let newcache =
-        cache::refresh_cache(prog_report, config::root_dirs().to_vec()).await?;
+    let mut newcache = cache::refresh_cache(
+        prog_report,
+        config::root_dirs().to_vec(),
+        config::filters(),
+    )
+    .await?;
+    newcache.keep_media(old);
     let newcache = cache::write_cache(&cache_file(), newcache).await?;
     log::info!("Refreshing cache done");
     Ok(newcache)
 }
 
 pub async fn refresh_cache_at_init() -> FilerResult<()> {
-    refresh_cache(|_| async {}).await.map(|_| ())
+    let old = read_cache(&cache_file()).await.unwrap_or_default();
+    refresh_cache(&old, |_| async {}).await.map(|_| ())
 }
 
 pub async fn read_cache(cache_file: &Path) -> FilerResult<Cache> {
     let cache = match cache::read_cache(cache_file).await {
-        Ok(c) if c.is_outdated(config::root_dirs()) => {
+        Ok(c) if c.is_outdated(config::root_dirs(), &config::filters()) => {
             log::info!("Saved cache is outdated");
             Ok(Cache::default())
         }
+        Err(FilerError::CacheVersion(v)) => {
+            log::warn!(
+                "Saved cache is from version {v} instead of {}, the roots have to be \
+                 scanned again",
+                cache::CACHE_VERSION
+            );
+            Ok(Cache::default())
+        }
+        Err(FilerError::Bincode(e)) => {
+            log::info!("Saved cache could not be read, probably outdated: {e}");
+            Ok(Cache::default())
+        }
         Ok(c) => Ok(c),
         Err(FilerError::IoError(ioe)) if ioe.kind() == io::ErrorKind::NotFound => {
             log::info!("There is no cache yet");
@@ -1,22 +1,32 @@
 mod scan;
 
 use itertools::Itertools;
-use std::time::SystemTime;
+use protocol::util::FileId;
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-pub use scan::{read_cache, refresh_cache, write_cache};
+use crate::{config::Filter, mpv::probe::Media};
+
+use super::{FilerError, FilerResult};
+
+pub use scan::{
+    read_cache, refresh_cache, refresh_dir, refresh_subtree, write_cache, CACHE_VERSION,
+};
 
 /// A cache of all files and directories from a list of source directories called "roots".
 /// The vectors in this struct are sorted in some "standard" order, which in this case
 /// means: ascending order by their path. The vectors can not be modified, since there are
 /// `Pointer`s and other `usize`s pointing to locations in the vectors.
 ///
-/// All paths are required to be valid rust strings, i.e., be valid UTF-8. This makes it
-/// easier to use on the client, and the libmpv crate (v2.0.1) needs them to be `String`s
-/// anyway (not anymore when i created my own libmpv). But this is a limitation that
-/// should be fixed in the future, i.e., use `PathBuf` instead.
-// TODO: make this partially updateable, i.e., update the files of a subfolder
+/// Paths are stored as they are on disk, which means that they don't have to be valid
+/// UTF-8. Each entry also has a lossy UTF-8 version of its path that is used whenever the
+/// path is to be shown or searched in.
 // TODO: https://docs.rs/slotmap/latest/slotmap/
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
 pub struct Cache {
     /// All files found, sorted in ascending order by their path relative to their
     /// respective root.
@@ -28,17 +38,33 @@ pub struct Cache {
     root_dir: Vec<Pointer>,
     /// The date when this cache was created.
     updated: Option<SystemTime>,
+    /// Changes every time the contents change, so that old `FileId`s can be detected.
+    generation: u64,
     /// The paths of all roots
     roots: Vec<String>,
+    /// The filters used when scanning each root
+    filters: Vec<Filter>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(super) struct CacheEntry {
-    relative_path: String,
+    relative_path: OsString,
+    /// `relative_path`, but lossily converted to UTF-8
+    display_path: String,
     root: usize,
+    /// What is in the file, `None` if it has not been probed yet
+    media: Option<Probed>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// What probing a file found out
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(super) enum Probed {
+    Media(Media),
+    /// It is not probed again unless the file changes
+    Failed,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(super) struct CacheDirEntry {
     entry: CacheEntry,
     children: Vec<Pointer>,
@@ -47,7 +73,7 @@ pub(super) struct CacheDirEntry {
 // NOTE: mainly here for less copying in the function `link`.
 #[derive(Debug, Hash, PartialEq, Eq)]
 struct CacheEntryBorrowed<'a> {
-    relative_path: &'a str,
+    relative_path: &'a OsStr,
     root: usize,
 }
 
@@ -58,16 +84,18 @@ pub enum Pointer {
 }
 
 impl CacheEntry {
-    fn new(relative_path: String, root: usize) -> Self {
-        assert!(relative_path.starts_with("/"));
+    fn new(relative_path: OsString, root: usize) -> Self {
+        assert!(Path::new(&relative_path).has_root());
         Self {
+            display_path: relative_path.to_string_lossy().into_owned(),
             relative_path,
             root,
+            media: None,
         }
     }
 
     fn new_root(root: usize) -> Self {
-        Self::new("/".to_string(), root)
+        Self::new("/".into(), root)
     }
 
     pub(super) fn is_root(&self) -> bool {
@@ -78,15 +106,26 @@ impl CacheEntry {
         self.root
     }
 
-    pub(super) fn path_relative_root(&self) -> &str {
+    pub(super) fn path_relative_root(&self) -> &OsStr {
         &self.relative_path
     }
 
-    /// The character index in `path_relative_root` where the basename starts, i.e., the
-    /// last path separator.
+    pub(super) fn display_path(&self) -> &str {
+        &self.display_path
+    }
+
+    pub(super) fn media(&self) -> Option<&Media> {
+        match &self.media {
+            Some(Probed::Media(media)) => Some(media),
+            Some(Probed::Failed) | None => None,
+        }
+    }
+
+    /// The character index in `display_path` where the basename starts, i.e., the last
+    /// path separator.
     pub(super) fn basename_char(&self) -> usize {
         match self
-            .path_relative_root()
+            .display_path()
             .chars()
             .enumerate()
             .filter(|&(_, c)| c == std::path::MAIN_SEPARATOR)
@@ -104,7 +143,9 @@ impl CacheEntry {
 
         Some(CacheEntryBorrowed {
             root: self.root,
-            relative_path: crate::util::dirname(&self.relative_path).unwrap_or("/"),
+            relative_path: Path::new(&self.relative_path)
+                .parent()
+                .map_or(OsStr::new("/"), Path::as_os_str),
         })
     }
 
@@ -117,7 +158,7 @@ impl CacheEntry {
 }
 
 impl CacheDirEntry {
-    fn new(relative_path: String, root: usize) -> Self {
+    fn new(relative_path: OsString, root: usize) -> Self {
         Self {
             entry: CacheEntry::new(relative_path, root),
             children: vec![],
@@ -140,7 +181,8 @@ impl CacheDirEntry {
             pub(super) fn root(&self) -> usize;
             #[allow(dead_code)]
             pub(super) fn is_root(&self) -> bool;
-            pub(super) fn path_relative_root(&self) -> &str;
+            pub(super) fn path_relative_root(&self) -> &OsStr;
+            pub(super) fn display_path(&self) -> &str;
             #[call(borrow)]
             fn borrow_cache_entry(&self) -> CacheEntryBorrowed<'_>;
             fn parent(&self) -> Option<CacheEntryBorrowed<'_>>;
@@ -158,7 +200,7 @@ impl CacheDirEntry {
 
 impl AsRef<str> for CacheEntry {
     fn as_ref(&self) -> &str {
-        self.path_relative_root()
+        self.display_path()
     }
 }
 
@@ -167,13 +209,18 @@ impl Cache {
         files: Vec<CacheEntry>,
         dirs: Vec<CacheDirEntry>,
         roots: Vec<String>,
+        filters: Vec<Filter>,
         root_dir: Vec<Pointer>,
     ) -> Self {
+        assert_eq!(roots.len(), filters.len(), "every root must have a filter");
+        let now = SystemTime::now();
         let cache = Self {
             files,
             dirs,
-            updated: Some(SystemTime::now()),
+            updated: Some(now),
+            generation: next_generation(now),
             roots,
+            filters,
             root_dir,
         };
 
@@ -264,8 +311,9 @@ impl Cache {
         &self.roots
     }
 
-    pub fn is_outdated(&self, roots: &[String]) -> bool {
-        self.roots != roots
+    /// Whether the cache was created with other roots or filters than these.
+    pub fn is_outdated(&self, roots: &[String], filters: &[Filter]) -> bool {
+        self.roots != roots || self.filters != filters
     }
 
     pub(super) fn deref(&self, pointer: Pointer) -> Option<&CacheEntry> {
@@ -282,6 +330,86 @@ impl Cache {
         }
     }
 
+    /// Find the directory at `path` relative to `root`.
+    pub(super) fn find_dir(&self, root: usize, path: &OsStr) -> Option<usize> {
+        let start = self
+            .dirs
+            .partition_point(|dir| dir.path_relative_root() < path);
+        self.dirs[start..]
+            .iter()
+            .take_while(|dir| dir.path_relative_root() == path)
+            .position(|dir| dir.root() == root)
+            .map(|i| start + i)
+    }
+
+    /// The files that have not been probed yet, as their root and path relative to it.
+    pub(super) fn unprobed(&self) -> impl Iterator<Item = (usize, &OsStr)> {
+        self.files
+            .iter()
+            .filter(|file| file.media.is_none())
+            .map(|file| (file.root(), file.path_relative_root()))
+    }
+
+    /// Set what is in the file at `index`.
+    pub(super) fn set_media(&mut self, index: usize, media: Probed) {
+        if let Some(file) = self.files.get_mut(index) {
+            file.media = Some(media);
+        }
+    }
+
+    /// Forget what is in the file at `index`, so that it is probed again.
+    pub(super) fn forget_media(&mut self, index: usize) {
+        if let Some(file) = self.files.get_mut(index) {
+            file.media = None;
+        }
+    }
+
+    /// Copy what is known to be in the files from `old`, so that they don't have to be
+    /// probed again. Files are matched by their root and path.
+    pub(super) fn keep_media(&mut self, old: &Cache) {
+        for file in self.files.iter_mut() {
+            let Some(root) = self
+                .roots
+                .get(file.root)
+                .and_then(|root| old.roots.iter().position(|r| r == root))
+            else {
+                continue;
+            };
+            if let Some(i) = old.find_file(root, &file.relative_path) {
+                file.media = old.files[i].media.clone();
+            }
+        }
+    }
+
+    /// Find the file at `path` relative to `root`.
+    pub fn find_file(&self, root: usize, path: &OsStr) -> Option<usize> {
+        let start = self
+            .files
+            .partition_point(|file| file.path_relative_root() < path);
+        self.files[start..]
+            .iter()
+            .take_while(|file| file.path_relative_root() == path)
+            .position(|file| file.root() == root)
+            .map(|i| start + i)
+    }
+
+    /// An id of the file at `index` that the client can use to refer to it.
+    pub fn file_id(&self, index: usize) -> FileId {
+        FileId::new(index, self.generation)
+    }
+
+    /// The root and the path relative to it of the file that `id` refers to.
+    pub fn resolve(&self, id: FileId) -> FilerResult<(usize, &OsStr)> {
+        if id.generation() != self.generation {
+            return Err(FilerError::OldFileId);
+        }
+
+        self.files
+            .get(id.index())
+            .map(|file| (file.root(), file.path_relative_root()))
+            .ok_or_else(|| FilerError::NotInCache(format!("file {}", id.index())))
+    }
+
     #[allow(dead_code)]
     pub(super) fn deref_file(&self, pointer: Pointer) -> Option<&CacheEntry> {
         match pointer {
@@ -298,3 +426,18 @@ impl Cache {
             .expect("there will always be a root (lich king)")
     }
 }
+
+/// A generation that is newer than all previous ones, also across restarts as long as the
+/// clock doesn't go backwards.
+fn next_generation(now: SystemTime) -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let last = LAST
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(now.max(last + 1))
+        })
+        .expect("the closure always returns Some");
+    now.max(last + 1)
+}
@@ -1,10 +1,14 @@
 use std::{
     collections::HashMap,
+    ffi::{OsStr, OsString},
     fs::{create_dir_all, File},
+    io,
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
 };
 
 use futures_util::{stream::FuturesUnordered, StreamExt};
+use globset::GlobSet;
 use itertools::Itertools;
 use protocol::to_client::front::filesearch;
 use std::future::Future;
@@ -12,24 +16,73 @@ use tokio::task::spawn_blocking;
 use walkdir::{DirEntry, Error as WalkdirError, WalkDir};
 
 use crate::{
+    config::Filter,
     filer::{
-        cache::{CacheDirEntry, CacheEntry, Pointer},
+        cache::{CacheDirEntry, CacheEntry, Pointer, Probed},
         FilerError, FilerResult,
     },
-    util::join_handle_wait_take,
+    util::{absolute, join_handle_wait_take},
 };
 
 use super::{Cache, CacheEntryBorrowed};
 
-// TODO: move to config
-const EXT_WHITELIST: &[&str] = &[".mp4", ".mkv", ".wmv", ".webm", ".avi"];
+/// A [`Filter`] that is ready to be used on files
+struct EntryFilter<'a> {
+    filter: &'a Filter,
+    ignore: GlobSet,
+}
+
+impl<'a> EntryFilter<'a> {
+    fn new(filter: &'a Filter) -> FilerResult<Self> {
+        Ok(Self {
+            filter,
+            ignore: filter.ignore_set()?,
+        })
+    }
+
+    fn includes(&self, relative_path: &OsStr, de: &DirEntry) -> io::Result<bool> {
+        if !self.includes_path(relative_path) {
+            return Ok(false);
+        }
+
+        if self.filter.min_size > 0 {
+            match de.metadata() {
+                Ok(meta) => return Ok(meta.len() >= self.filter.min_size),
+                Err(e) => {
+                    log::error!("Failed to read the size of '{:?}': {}", de.path(), e);
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn includes_path(&self, relative_path: &OsStr) -> bool {
+        let path = Path::new(relative_path);
+        has_whitelisted_extension(path, &self.filter.extensions)
+            && !self.ignore.is_match(path.strip_prefix("/").unwrap_or(path))
+    }
+}
+
+fn entry_filters(filters: &[Filter]) -> FilerResult<Vec<EntryFilter<'_>>> {
+    filters.iter().map(EntryFilter::new).collect()
+}
+
+/// Written first in the cache file, and changed whenever what is saved in the cache
+/// changes, since an old cache can't be read.
+pub const CACHE_VERSION: u32 = 2;
 
 pub async fn read_cache(path: &Path) -> FilerResult<Cache> {
     // NOTE: tokio is doing this itself, i.e., creating a PathBuf
     // https://docs.rs/tokio/1.26.0/src/tokio/fs/read.rs.html#48-51
     let path = path.to_owned();
     join_handle_wait_take(spawn_blocking(move || {
-        let file = File::open(&path)?;
+        let mut file = File::open(&path)?;
+        let version: u32 = bincode::deserialize_from(&mut file)?;
+        if version != CACHE_VERSION {
+            return Err(FilerError::CacheVersion(version));
+        }
         bincode::deserialize_from(file).map_err(|e| e.into())
     }))
     .await
@@ -48,6 +101,7 @@ pub async fn write_cache(path: &Path, contents: Cache) -> FilerResult<Cache> {
         }
         let mut file = File::create(path)?;
 
+        bincode::serialize_into(&mut file, &CACHE_VERSION)?;
         bincode::serialize_into(&mut file, &contents)?;
 
         file.sync_all()?;
@@ -59,6 +113,7 @@ pub async fn write_cache(path: &Path, contents: Cache) -> FilerResult<Cache> {
 pub async fn refresh_cache<F, Fut>(
     mut prog_report: F,
     roots: Vec<String>,
+    filters: Vec<Filter>,
 ) -> FilerResult<Cache>
 where
     F: FnMut(filesearch::refreshing::Refreshing) -> Fut,
@@ -92,6 +147,7 @@ where
         deep.files.into_iter().chain(shallow.files),
         deep.dirs.into_iter().chain(shallow.dirs),
         roots,
+        filters,
         num_errors,
         &mut prog_report,
         shallow_dirs_len,
@@ -140,10 +196,12 @@ where
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn create_cache_from_files<F, Fut>(
     files: impl Iterator<Item = (usize, DirEntry)>,
     dirs: impl Iterator<Item = (usize, DirEntry)>,
     roots: Vec<String>,
+    filters: Vec<Filter>,
     mut num_errors: usize,
     mut prog_report: F,
     num_dirs: usize,
@@ -153,28 +211,45 @@ where
     F: FnMut(filesearch::refreshing::Refreshing) -> Fut,
     Fut: Future<Output = ()>,
 {
-    let mut cache_files: Vec<CacheEntry> = files
-        .filter_map(|(i, de)| match create_cache_entry(de, i, &roots) {
-            Ok(None) => None,
-            Ok(Some(ce)) => Some(ce),
-            Err(()) => {
-                num_errors += 1;
-                None
+    let entry_filters = entry_filters(&filters)?;
+    let cache_files: Vec<CacheEntry> = files
+        .filter_map(|(i, de)| {
+            match create_cache_entry(de, i, &roots, &entry_filters[i]) {
+                Ok(None) => None,
+                Ok(Some(ce)) => Some(ce),
+                Err(_) => {
+                    num_errors += 1;
+                    None
+                }
             }
         })
         .collect();
 
     let mut cache_dirs: Vec<CacheDirEntry> = dirs
-        .filter_map(|(i, de)| match create_cache_dir_entry(de, i, &roots) {
-            Ok(ce) => Some(ce),
-            Err(()) => {
-                num_errors += 1;
-                None
-            }
-        })
+        .map(|(i, de)| create_cache_dir_entry(de, i, &roots))
         .collect();
     cache_dirs.extend(surface_scan(&roots));
 
+    prog_report(make_refreshing(
+        num_dirs,
+        num_dirs,
+        &roots,
+        root_status,
+        num_errors,
+        true,
+    ))
+    .await;
+
+    Ok(assemble_cache(cache_files, cache_dirs, roots, filters))
+}
+
+/// Create a cache out of unordered entries, the roots must be among the dirs.
+fn assemble_cache(
+    mut cache_files: Vec<CacheEntry>,
+    mut cache_dirs: Vec<CacheDirEntry>,
+    roots: Vec<String>,
+    filters: Vec<Filter>,
+) -> Cache {
     cache_files
         .sort_unstable_by(|e1, e2| e1.path_relative_root().cmp(e2.path_relative_root()));
     cache_dirs
@@ -206,22 +281,161 @@ where
         .map(|i| Pointer::Dir(i))
         .collect();
 
-    prog_report(make_refreshing(
-        num_dirs,
-        num_dirs,
-        &roots,
-        root_status,
-        num_errors,
-        true,
-    ))
-    .await;
+    Cache::new(cache_files, cache_dirs, roots, filters, root_dir_pointers)
+}
+
+/// Rescan the directory at `path` relative to `root`, and everything below it. The rest
+/// of the cache is left as it is.
+pub async fn refresh_subtree(
+    cache: Cache,
+    root: usize,
+    path: &OsStr,
+) -> FilerResult<Cache> {
+    let abs_path = absolute(root_path(&cache, root, path)?, path);
+    log::info!("Rescanning {abs_path:?}...");
+
+    let walked = walk(WalkDir::new(abs_path).min_depth(1)).await;
+    rescanned(cache, root, path, walked, &[])
+}
+
+/// Rescan what is directly in the directory at `path` relative to `root`. The directories
+/// in it that were already in the cache are left as they are, while new ones are scanned
+/// with everything below them.
+pub async fn refresh_dir(cache: Cache, root: usize, path: &OsStr) -> FilerResult<Cache> {
+    let abs_path = absolute(root_path(&cache, root, path)?, path);
+    log::info!("Rescanning the contents of {abs_path:?}...");
+
+    let mut walked = walk(WalkDir::new(abs_path).min_depth(1).max_depth(1)).await;
+    let mut kept = Vec::new();
+    for de in walked.new_dirs.clone() {
+        let relative_path = relative_to_root(&de, root, &cache.roots);
+        if cache.find_dir(root, &relative_path).is_some() {
+            kept.push(relative_path);
+        } else {
+            walked.extend(walk(WalkDir::new(de.path()).min_depth(1)).await);
+        }
+    }
+
+    rescanned(cache, root, path, walked, &kept)
+}
+
+/// The path of `root`, if `path` is a directory in it.
+fn root_path<'a>(cache: &'a Cache, root: usize, path: &OsStr) -> FilerResult<&'a str> {
+    let root_path = cache
+        .roots
+        .get(root)
+        .ok_or_else(|| FilerError::NotInCache(format!("root {root}")))?;
+    if cache.find_dir(root, path).is_none() {
+        return Err(FilerError::NotInCache(path.to_string_lossy().into_owned()));
+    }
+    Ok(root_path)
+}
 
-    Ok(Cache::new(
-        cache_files,
-        cache_dirs,
+/// Put what was found below `path` into the cache, see [`splice`].
+fn rescanned(
+    cache: Cache,
+    root: usize,
+    path: &OsStr,
+    walked: Walked,
+    kept: &[OsString],
+) -> FilerResult<Cache> {
+    let Walked {
+        new_files,
+        new_dirs,
+        new_others,
+        new_errors,
+    } = walked;
+
+    for other in new_others {
+        log::warn!("Found unknown file: {other:?}");
+    }
+
+    for err in new_errors.iter() {
+        log::error!("Error on file: {err:?}");
+    }
+
+    let mut num_errors = new_errors.len();
+    let filter = EntryFilter::new(&cache.filters[root])?;
+    let files = new_files
+        .into_iter()
+        .filter_map(
+            |de| match create_cache_entry(de, root, &cache.roots, &filter) {
+                Ok(ce) => ce,
+                Err(_) => {
+                    num_errors += 1;
+                    None
+                }
+            },
+        )
+        .collect();
+    let dirs = new_dirs
+        .into_iter()
+        .map(|de| create_cache_dir_entry(de, root, &cache.roots))
+        .collect();
+
+    if num_errors > 0 {
+        log::warn!("Rescanned {path:?} with {num_errors} errors");
+    }
+    Ok(splice(cache, root, path, files, dirs, kept))
+}
+
+/// Replace everything below the directory `path` in `root` with `files` and `dirs`,
+/// except what is below the directories in `kept`.
+fn splice(
+    cache: Cache,
+    root: usize,
+    path: &OsStr,
+    files: Vec<CacheEntry>,
+    dirs: Vec<CacheDirEntry>,
+    kept: &[OsString],
+) -> Cache {
+    let strictly_below = |entry: &CacheEntry, dir: &OsStr| {
+        entry.path_relative_root() != dir
+            && Path::new(entry.path_relative_root()).starts_with(dir)
+    };
+    let is_below = |entry: &CacheEntry| {
+        entry.root() == root
+            && strictly_below(entry, path)
+            && !kept.iter().any(|dir| strictly_below(entry, dir))
+    };
+
+    let Cache {
+        files: old_files,
+        dirs: old_dirs,
+        updated,
         roots,
-        root_dir_pointers,
-    ))
+        filters,
+        ..
+    } = cache;
+
+    let (below, old_files): (Vec<_>, Vec<_>) =
+        old_files.into_iter().partition(|f| is_below(f));
+    // NOTE: files that are still there don't have to be probed again
+    let mut old_media: HashMap<OsString, Probed> = below
+        .into_iter()
+        .filter_map(|f| Some((f.relative_path, f.media?)))
+        .collect();
+    let cache_files = old_files
+        .into_iter()
+        .chain(files.into_iter().map(|f| CacheEntry {
+            media: old_media.remove(&f.relative_path),
+            ..f
+        }))
+        .collect();
+    let cache_dirs = old_dirs
+        .into_iter()
+        .filter(|d| !is_below(d.cache_entry()))
+        .map(|d| CacheDirEntry {
+            children: Vec::new(),
+            ..d
+        })
+        .chain(dirs)
+        .collect();
+
+    Cache {
+        updated,
+        ..assemble_cache(cache_files, cache_dirs, roots, filters)
+    }
 }
 
 fn link(
@@ -286,6 +500,15 @@ struct Walked {
     new_others: Vec<DirEntry>,
 }
 
+impl Walked {
+    fn extend(&mut self, other: Walked) {
+        self.new_errors.extend(other.new_errors);
+        self.new_files.extend(other.new_files);
+        self.new_dirs.extend(other.new_dirs);
+        self.new_others.extend(other.new_others);
+    }
+}
+
 async fn walk(walker: WalkDir) -> Walked {
     join_handle_wait_take(spawn_blocking(move || {
         let mut new_errors = Vec::new();
@@ -316,49 +539,30 @@ fn create_cache_entry(
     de: DirEntry,
     i: usize,
     roots: &[String],
-) -> Result<Option<CacheEntry>, ()> {
-    match de.into_path().into_os_string().into_string() {
-        Ok(path) if has_whitelisted_extension(&path) => Ok(Some(CacheEntry::new(
-            {
-                let r = roots
-                    .get(i)
-                    .expect("i is from enumerate, i.e. always in range");
-                path.strip_prefix(r)
-                    .expect("Path must begin with this root")
-                    .to_string()
-            },
-            i,
-        ))),
-        Ok(_) => Ok(None),
-        Err(path) => {
-            log::error!("Failed to convert '{:?} to a String", path);
-            Err(())
-        }
-    }
+    filter: &EntryFilter,
+) -> io::Result<Option<CacheEntry>> {
+    let relative_path = relative_to_root(&de, i, roots);
+    Ok(filter
+        .includes(&relative_path, &de)?
+        .then(|| CacheEntry::new(relative_path, i)))
 }
 
-fn create_cache_dir_entry(
-    de: DirEntry,
-    i: usize,
-    roots: &[String],
-) -> Result<CacheDirEntry, ()> {
-    match de.into_path().into_os_string().into_string() {
-        Ok(path) => Ok(CacheDirEntry::new(
-            {
-                let r = roots
-                    .get(i)
-                    .expect("i is from enumerate, i.e. always in range");
-                path.strip_prefix(r)
-                    .expect("Path must begin with this root")
-                    .to_string()
-            },
-            i,
-        )),
-        Err(path) => {
-            log::error!("Failed to convert '{:?} to a String", path);
-            Err(())
-        }
-    }
+fn create_cache_dir_entry(de: DirEntry, i: usize, roots: &[String]) -> CacheDirEntry {
+    CacheDirEntry::new(relative_to_root(&de, i, roots), i)
+}
+
+/// The path of `de` relative to its root, i.e., starting with a slash.
+fn relative_to_root(de: &DirEntry, i: usize, roots: &[String]) -> OsString {
+    let r = roots
+        .get(i)
+        .expect("i is from enumerate, i.e. always in range");
+    let relative = de
+        .path()
+        .as_os_str()
+        .as_bytes()
+        .strip_prefix(r.as_bytes())
+        .expect("Path must begin with this root");
+    OsStr::from_bytes(relative).to_owned()
 }
 
 struct Scan {
@@ -488,8 +692,10 @@ fn surface_scan(roots: &[String]) -> Vec<CacheDirEntry> {
         .collect()
 }
 
-fn has_whitelisted_extension(path: &str) -> bool {
-    EXT_WHITELIST.iter().any(|ext| path.ends_with(ext))
+fn has_whitelisted_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| e == ext))
 }
 
 fn make_refreshing(
@@ -517,3 +723,245 @@ fn make_refreshing(
     };
     msg
 }
+
+#[cfg(test)]
+mod test {
+    use crate::mpv::probe::Media;
+
+    use super::*;
+
+    fn file(root: usize, path: &str) -> CacheEntry {
+        CacheEntry::new(path.into(), root)
+    }
+
+    fn dir(root: usize, path: &str) -> CacheDirEntry {
+        CacheDirEntry::new(path.into(), root)
+    }
+
+    fn example() -> Cache {
+        assemble_cache(
+            vec![
+                file(0, "/a/x.mkv"),
+                file(0, "/a/b/y.mkv"),
+                file(0, "/z.mkv"),
+                file(1, "/a/x.mkv"),
+            ],
+            vec![
+                CacheDirEntry::new_root(0),
+                CacheDirEntry::new_root(1),
+                dir(0, "/a"),
+                dir(0, "/a/b"),
+                dir(1, "/a"),
+            ],
+            vec!["/r0".to_string(), "/r1".to_string()],
+            vec![filter(&[]), filter(&[])],
+        )
+    }
+
+    fn filter(ignore: &[&str]) -> Filter {
+        Filter {
+            extensions: vec!["mkv".to_string()],
+            ignore: ignore.iter().map(|s| s.to_string()).collect(),
+            min_size: 0,
+        }
+    }
+
+    fn files(cache: &Cache) -> Vec<(usize, &str)> {
+        let mut files: Vec<_> = cache
+            .files()
+            .map(|f| (f.root(), f.display_path()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn children<'a>(cache: &'a Cache, root: usize, path: &str) -> Vec<&'a str> {
+        let dir = cache.find_dir(root, OsStr::new(path)).expect("dir exists");
+        cache
+            .deref_dir(Pointer::Dir(dir))
+            .expect("is a dir")
+            .children()
+            .iter()
+            .map(|&p| cache.deref(p).expect("valid").display_path())
+            .collect()
+    }
+
+    #[test]
+    fn splice_subdir() {
+        let cache = splice(
+            example(),
+            0,
+            OsStr::new("/a"),
+            vec![file(0, "/a/new.mkv")],
+            vec![dir(0, "/a/c")],
+            &[],
+        );
+
+        assert_eq!(
+            vec![(0, "/a/new.mkv"), (0, "/z.mkv"), (1, "/a/x.mkv")],
+            files(&cache)
+        );
+        assert_eq!(vec!["/a/c", "/a/new.mkv"], children(&cache, 0, "/a"));
+        assert_eq!(vec!["/a", "/z.mkv"], children(&cache, 0, "/"));
+        assert_eq!(vec!["/a/x.mkv"], children(&cache, 1, "/a"));
+        assert_eq!(None, cache.find_dir(0, OsStr::new("/a/b")));
+    }
+
+    #[test]
+    fn splice_keeps_subdirs() {
+        let cache = splice(
+            example(),
+            0,
+            OsStr::new("/"),
+            vec![file(0, "/w.mkv")],
+            vec![dir(0, "/a")],
+            &["/a".into()],
+        );
+
+        assert_eq!(
+            vec![
+                (0, "/a/b/y.mkv"),
+                (0, "/a/x.mkv"),
+                (0, "/w.mkv"),
+                (1, "/a/x.mkv")
+            ],
+            files(&cache)
+        );
+        assert_eq!(vec!["/a", "/w.mkv"], children(&cache, 0, "/"));
+        assert_eq!(vec!["/a/b", "/a/x.mkv"], children(&cache, 0, "/a"));
+    }
+
+    #[test]
+    fn splice_root() {
+        let cache = splice(
+            example(),
+            1,
+            OsStr::new("/"),
+            vec![file(1, "/w.mkv")],
+            vec![],
+            &[],
+        );
+
+        assert_eq!(
+            vec![
+                (0, "/a/b/y.mkv"),
+                (0, "/a/x.mkv"),
+                (0, "/z.mkv"),
+                (1, "/w.mkv")
+            ],
+            files(&cache)
+        );
+        assert_eq!(vec!["/w.mkv"], children(&cache, 1, "/"));
+        assert_eq!(None, cache.find_dir(1, OsStr::new("/a")));
+        assert_eq!(vec!["/a/b", "/a/x.mkv"], children(&cache, 0, "/a"));
+    }
+
+    #[test]
+    fn splice_keeps_media() {
+        let media = Media {
+            duration: Some(std::time::Duration::from_secs(60)),
+            ..Media::default()
+        };
+        let mut cache = example();
+        for path in ["/a/x.mkv", "/a/b/y.mkv"] {
+            let i = cache.find_file(0, OsStr::new(path)).unwrap();
+            cache.set_media(i, Probed::Media(media.clone()));
+        }
+
+        let cache = splice(
+            cache,
+            0,
+            OsStr::new("/a"),
+            vec![file(0, "/a/x.mkv"), file(0, "/a/new.mkv")],
+            vec![],
+            &[],
+        );
+        let media_of = |path: &str| {
+            let i = cache.find_file(0, OsStr::new(path)).unwrap();
+            cache.files[i].media().cloned()
+        };
+        assert_eq!(Some(media.clone()), media_of("/a/x.mkv"));
+        assert_eq!(None, media_of("/a/new.mkv"));
+        assert_eq!(None, media_of("/z.mkv"));
+    }
+
+    #[test]
+    fn keep_media_by_root_path() {
+        let media = Media {
+            resolution: Some((1920, 1080)),
+            ..Media::default()
+        };
+        let mut old = example();
+        let i = old.find_file(1, OsStr::new("/a/x.mkv")).unwrap();
+        old.set_media(i, Probed::Media(media.clone()));
+
+        // NOTE: the roots have switched places
+        let mut new = assemble_cache(
+            vec![file(0, "/a/x.mkv"), file(1, "/a/x.mkv")],
+            vec![
+                CacheDirEntry::new_root(0),
+                CacheDirEntry::new_root(1),
+                dir(0, "/a"),
+                dir(1, "/a"),
+            ],
+            vec!["/r1".to_string(), "/r0".to_string()],
+            vec![filter(&[]), filter(&[])],
+        );
+        new.keep_media(&old);
+        let media_of = |root| {
+            let i = new.find_file(root, OsStr::new("/a/x.mkv")).unwrap();
+            new.files[i].media().cloned()
+        };
+        assert_eq!(Some(media), media_of(0));
+        assert_eq!(None, media_of(1));
+    }
+
+    #[test]
+    fn splice_similar_names() {
+        let cache = assemble_cache(
+            vec![file(0, "/a/x.mkv"), file(0, "/ab/y.mkv")],
+            vec![CacheDirEntry::new_root(0), dir(0, "/a"), dir(0, "/ab")],
+            vec!["/r0".to_string()],
+            vec![filter(&[])],
+        );
+        let cache = splice(cache, 0, OsStr::new("/a"), vec![], vec![], &[]);
+
+        assert_eq!(vec![(0, "/ab/y.mkv")], files(&cache));
+    }
+
+    #[test]
+    fn entry_filter_paths() {
+        let filter = filter(&["**/Sample/**", "*.part"]);
+        let filter = EntryFilter::new(&filter).unwrap();
+        assert!(filter.includes_path(OsStr::new("/a/x.mkv")));
+        assert!(filter.includes_path(OsStr::new("/x.mkv")));
+        assert!(!filter.includes_path(OsStr::new("/x.mp4")));
+        assert!(!filter.includes_path(OsStr::new("/mkv")));
+        assert!(!filter.includes_path(OsStr::new("/a/Sample/x.mkv")));
+        assert!(!filter.includes_path(OsStr::new("/Sample/x.mkv")));
+        assert!(filter.includes_path(OsStr::new("/Samples/x.mkv")));
+        assert!(!filter.includes_path(OsStr::new("/a/x.mkv.part")));
+    }
+
+    #[test]
+    fn non_utf8_paths() {
+        let path = OsStr::from_bytes(b"/caf\xe9.mkv");
+        let entry = CacheEntry::new(path.to_owned(), 0);
+        assert_eq!(path, entry.path_relative_root());
+        assert_eq!("/caf\u{FFFD}.mkv", entry.display_path());
+
+        let filter = filter(&[]);
+        assert!(EntryFilter::new(&filter).unwrap().includes_path(path));
+    }
+
+    #[test]
+    fn file_ids() {
+        let cache = example();
+        let i = cache.find_file(0, OsStr::new("/z.mkv")).unwrap();
+        let id = cache.file_id(i);
+        assert_eq!((0, OsStr::new("/z.mkv")), cache.resolve(id).unwrap());
+
+        let cache = splice(cache, 1, OsStr::new("/"), vec![], vec![], &[]);
+        assert!(matches!(cache.resolve(id), Err(FilerError::OldFileId)));
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    ffi::OsString,
+    mem,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use tokio::{task::spawn_blocking, time::sleep};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config,
+    mpv::probe::probe,
+    util::{absolute, join_handle_wait_take, FutureCancel},
+};
+
+use super::{
+    cache::{self, Probed},
+    cache_file,
+    shared::SharedCache,
+};
+
+/// How often what has been found is saved in the cache while probing.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long to wait before looking for new files when everything has been probed.
+const IDLE: Duration = Duration::from_secs(60);
+
+/// A file, by the path of its root and its path relative to that root.
+type File = (String, OsString);
+
+/// Look at what is in every file in the cache, e.g., how long it is and what subtitles it
+/// has, and save that in the cache. Does nothing unless it is enabled in the config.
+pub async fn prober_actor(cache: SharedCache, canceltoken: CancellationToken) {
+    if !config::probe_media() {
+        log::debug!("Not probing the files since it is not configured");
+        return;
+    }
+
+    log::info!("Probing the files in the cache");
+    while !canceltoken.is_cancelled() {
+        let files = unprobed(&cache);
+        if files.is_empty() {
+            sleep(IDLE).cancellable(&canceltoken).await;
+            continue;
+        }
+
+        log::info!("Probing {} files", files.len());
+        let mut probed = Vec::new();
+        let mut saved = Instant::now();
+        for (root, path) in files {
+            let video = PathBuf::from(absolute(&root, &path));
+            let token = canceltoken.clone();
+            let res = {
+                let video = video.clone();
+                join_handle_wait_take(spawn_blocking(move || probe(&video, &token))).await
+            };
+            if canceltoken.is_cancelled() {
+                // NOTE: the probe was most likely interrupted, so the result is not real
+                break;
+            }
+            let media = match res {
+                Ok(media) => Probed::Media(media),
+                Err(e) => {
+                    log::debug!("Failed to probe {video:?}: {e:?}");
+                    Probed::Failed
+                }
+            };
+            probed.push(((root, path), media));
+
+            if saved.elapsed() >= SAVE_INTERVAL {
+                if let Err(e) = save(&cache, mem::take(&mut probed)).await {
+                    log::error!("Failed to save the probed files, stopping: {e}");
+                    return;
+                }
+                saved = Instant::now();
+            }
+        }
+
+        if let Err(e) = save(&cache, probed).await {
+            log::error!("Failed to save the probed files, stopping: {e}");
+            return;
+        }
+    }
+    log::info!("Stopped probing the files");
+}
+
+/// The files that have not been probed yet.
+fn unprobed(cache: &SharedCache) -> Vec<File> {
+    let cache = cache.get();
+    let roots = cache.roots_path();
+    cache
+        .unprobed()
+        .map(|(root, path)| (roots[root].clone(), path.to_owned()))
+        .collect()
+}
+
+async fn save(
+    cache: &SharedCache,
+    probed: Vec<(File, Probed)>,
+) -> super::FilerResult<()> {
+    if probed.is_empty() {
+        return Ok(());
+    }
+
+    cache
+        .update(|mut cache| async move {
+            for ((root, path), media) in probed {
+                // NOTE: the cache might have been refreshed while probing
+                let index = cache
+                    .roots_path()
+                    .iter()
+                    .position(|r| *r == root)
+                    .and_then(|root| cache.find_file(root, &path));
+                if let Some(index) = index {
+                    cache.set_media(index, media);
+                }
+            }
+            cache::write_cache(&cache_file(), cache).await
+        })
+        .await
+        .map(|_| ())
+}
@@ -1,12 +1,72 @@
-use protocol::to_client::front::filesearch;
+use std::time::Duration;
 
-use crate::filer::cache::Cache;
+use protocol::{
+    to_client::front::filesearch,
+    to_server::fscontrol::search_ctrl::search::{Search, SearchMode},
+};
+
+use crate::{
+    filer::cache::{Cache, CacheEntry},
+    mpv::probe::Media,
+    watch_later::WatchLater,
+    watched::Watched,
+};
 
 const NUM_SEARCH_RESULTS: usize = 30;
 
-pub fn search(query: String, cache: &Cache) -> filesearch::results::Results {
-    log::info!("Searching for: {}", query);
-    match searcher::search(&query, cache.files()) {
+/// A file in the cache, with what the filters in a search term can look at.
+struct Candidate<'a> {
+    entry: &'a CacheEntry,
+    root: &'a str,
+    watched: &'a Watched,
+}
+
+impl AsRef<str> for Candidate<'_> {
+    fn as_ref(&self) -> &str {
+        self.entry.display_path()
+    }
+}
+
+impl searcher::Candidate for Candidate<'_> {
+    fn root(&self) -> Option<&str> {
+        Some(self.root)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.entry.media().and_then(|media| media.duration)
+    }
+
+    fn has_subs(&self, lang: &str) -> Option<bool> {
+        self.entry.media().map(|media| media.has_subs(lang))
+    }
+
+    fn watched(&self) -> Option<bool> {
+        Some(
+            self.watched
+                .is_watched(self.root, self.entry.path_relative_root()),
+        )
+    }
+}
+
+pub fn search(
+    Search { query, mode }: Search,
+    cache: &Cache,
+    watched: &Watched,
+    watch_later: &WatchLater,
+) -> filesearch::results::Results {
+    log::info!("Searching for: {} ({:?})", query, mode);
+    let searcher_mode = match mode {
+        SearchMode::Swiper => searcher::SearchMode::Swiper,
+        SearchMode::Fuzzy => searcher::SearchMode::Fuzzy,
+        SearchMode::Literal => searcher::SearchMode::Literal,
+        SearchMode::Regex => searcher::SearchMode::Regex,
+    };
+    let candidates = cache.files().map(|entry| Candidate {
+        entry,
+        root: cache.root_path(entry),
+        watched,
+    });
+    match searcher::search(&query, searcher_mode, candidates) {
         Err(e) => {
             log::debug!(
                 "failed to search, '{}' could not be compiled cuz: {}",
@@ -16,7 +76,12 @@ pub fn search(query: String, cache: &Cache) -> filesearch::results::Results {
             filesearch::results::Results {
                 results: Vec::new(),
                 query,
-                query_valid: false,
+                mode,
+                error: Some(filesearch::results::QueryError {
+                    message: e.to_string(),
+                    start: e.span.start,
+                    end: e.span.end,
+                }),
             }
         }
         Ok(mut res) => {
@@ -25,12 +90,22 @@ pub fn search(query: String, cache: &Cache) -> filesearch::results::Results {
             let searchres = top
                 .iter_mut()
                 .map(|r| {
-                    let c_entry = r.get_inner();
+                    let Candidate {
+                        entry: c_entry,
+                        root: root_path,
+                        ..
+                    } = r.get_inner();
+                    let path = c_entry.path_relative_root();
                     filesearch::results::SearchResult {
-                        path: c_entry.path_relative_root().to_string(),
+                        path: c_entry.display_path().to_string(),
+                        // NOTE: the candidates are numbered in the order of the files
+                        id: cache.file_id(r.get_index()),
                         root: c_entry.root(),
                         indices: r.get_match().indices().to_vec(),
                         basename: c_entry.basename_char(),
+                        watched: watched.is_watched(root_path, path),
+                        progress: watch_later.progress(root_path, path),
+                        media: c_entry.media().map(Media::to_client),
                     }
                 })
                 .collect();
@@ -38,7 +113,8 @@ pub fn search(query: String, cache: &Cache) -> filesearch::results::Results {
             filesearch::results::Results {
                 results: searchres,
                 query,
-                query_valid: true,
+                mode,
+                error: None,
             }
         }
     }
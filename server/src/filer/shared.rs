@@ -0,0 +1,103 @@
+use std::{
+    ffi::OsStr,
+    future::Future,
+    sync::{Arc, RwLock},
+};
+
+use protocol::{to_client::front::filesearch, util::FileId};
+
+use super::{cache, cache::Cache, cache_file, read_cache, FilerError, FilerResult};
+
+/// The cache that everybody uses, kept in memory so that it only has to be read from disk
+/// once. Readers get a reference counted snapshot, which is never modified. Updates build
+/// a new cache, save it, and then replace the current one all at once, so a snapshot is
+/// always consistent even while the cache is being refreshed.
+#[derive(Clone)]
+pub struct SharedCache {
+    current: Arc<RwLock<Arc<Cache>>>,
+    /// Held while updating, so that concurrent updates don't overwrite each other.
+    updating: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SharedCache {
+    fn new(cache: Cache) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(cache))),
+            updating: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Read the cache from disk. Starts out empty if that fails.
+    pub async fn load() -> Self {
+        let cache = match read_cache(&cache_file()).await {
+            Ok(cache) => cache,
+            Err(e) => {
+                log::error!("Failed to read the cache, starting with an empty one: {e}");
+                Cache::default()
+            }
+        };
+        Self::new(cache)
+    }
+
+    /// The current cache.
+    pub fn get(&self) -> Arc<Cache> {
+        self.current
+            .read()
+            .expect("the lock is never held while panicking")
+            .clone()
+    }
+
+    /// Scan all roots, and replace the cache with the result.
+    pub async fn refresh<F, Fut>(&self, prog_report: F) -> FilerResult<Arc<Cache>>
+    where
+        F: FnMut(filesearch::refreshing::Refreshing) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        self.update(|old| async move { super::refresh_cache(&old, prog_report).await })
+            .await
+    }
+
+    /// Rescan `path` in `root`, and replace that part of the cache with the result.
+    pub async fn refresh_subtree(
+        &self,
+        root: usize,
+        path: &OsStr,
+    ) -> FilerResult<Arc<Cache>> {
+        self.update(|old| async move {
+            log::info!("Refreshing the cache of root={root}, path={path:?}");
+            let newcache = cache::refresh_subtree(old, root, path).await?;
+            let newcache = cache::write_cache(&cache_file(), newcache).await?;
+            log::info!("Refreshing the cache of {path:?} done");
+            Ok(newcache)
+        })
+        .await
+    }
+
+    /// Replace the cache with what `f` makes of a copy of the current one. `f` is
+    /// responsible for saving it.
+    pub async fn update<F, Fut>(&self, f: F) -> FilerResult<Arc<Cache>>
+    where
+        F: FnOnce(Cache) -> Fut,
+        Fut: Future<Output = FilerResult<Cache>>,
+    {
+        let _updating = self.updating.lock().await;
+        let old = Cache::clone(&self.get());
+        let new = Arc::new(f(old).await?);
+        *self
+            .current
+            .write()
+            .expect("the lock is never held while panicking") = new.clone();
+        Ok(new)
+    }
+
+    /// The id in the current cache of the file that `id` refers to in `cache`, which might
+    /// be an older snapshot.
+    pub fn reissue(&self, cache: &Cache, id: FileId) -> FilerResult<FileId> {
+        let (root, path) = cache.resolve(id)?;
+        let current = self.get();
+        current
+            .find_file(root, path)
+            .map(|i| current.file_id(i))
+            .ok_or_else(|| FilerError::NotInCache(path.to_string_lossy().into_owned()))
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{filer::cache::Pointer, util::basename};
+use std::ffi::{OsStr, OsString};
+
+use crate::{filer::cache::Pointer, mpv::probe::Media, util::basename};
 
 use super::cache::{Cache, CacheDirEntry};
 
@@ -14,11 +16,27 @@ pub enum Type {
     Root,
 }
 
+/// Where in the tree it is, without borrowing the cache. Used to get back to the same
+/// place in a refreshed cache.
+pub struct Location {
+    pub root: usize,
+    /// The paths relative to the root of all dirs down to the current one
+    pub dirs: Vec<OsString>,
+}
+
+impl Location {
+    pub fn current_dir(&self) -> &OsStr {
+        self.dirs.last().expect("a location is never empty")
+    }
+}
+
 pub struct File<'a> {
     pub name: &'a str,
-    pub path_relative_root: &'a str,
+    pub path_relative_root: &'a OsStr,
     pub ty: Type,
     pub id: usize,
+    /// What is in the file, if it has been probed
+    pub media: Option<&'a Media>,
 }
 
 impl<'a> Tree<'a> {
@@ -30,6 +48,18 @@ impl<'a> Tree<'a> {
         }
     }
 
+    /// Go to `location`, or as close to it as possible if some dirs no longer exist.
+    pub fn with_location(cache: &'a Cache, location: &Location) -> Self {
+        let mut tree = Self::new(cache);
+        for dir in &location.dirs {
+            match cache.find_dir(location.root, dir) {
+                Some(id) => tree.cd(id).expect("the id comes from the cache"),
+                None => break,
+            }
+        }
+        tree
+    }
+
     pub fn files(&self) -> impl Iterator<Item = File<'_>> {
         let pointers = self.top_pointers();
 
@@ -39,6 +69,7 @@ impl<'a> Tree<'a> {
                 .deref(*point)
                 .expect("all pointers in the cache are valid");
             let relative_root = entry.path_relative_root();
+            let display = entry.display_path();
             let (id, ty) = match point {
                 Pointer::File(i) => (*i, Type::Regular),
                 Pointer::Dir(i) => (*i, Type::Directory),
@@ -50,14 +81,16 @@ impl<'a> Tree<'a> {
                     name: self.cache.root_path(entry),
                     path_relative_root: relative_root,
                     id,
+                    media: entry.media(),
                 }
             } else {
-                let name = basename(relative_root).expect("is not root");
+                let name = basename(display).expect("is not root");
                 File {
                     ty,
                     name,
                     path_relative_root: relative_root,
                     id,
+                    media: entry.media(),
                 }
             }
         })
@@ -94,6 +127,17 @@ impl<'a> Tree<'a> {
         self.root
     }
 
+    pub fn location(&self) -> Option<Location> {
+        Some(Location {
+            root: self.root?,
+            dirs: self
+                .path
+                .iter()
+                .map(|dir| dir.path_relative_root().to_owned())
+                .collect(),
+        })
+    }
+
     pub fn breadcrumbs(&self) -> Vec<String> {
         let mut bread = Vec::new();
 
@@ -102,7 +146,7 @@ impl<'a> Tree<'a> {
         }
 
         bread.extend(self.path.iter().skip(1).map(|p| {
-            basename(p.path_relative_root())
+            basename(p.display_path())
                 .map(str::to_string)
                 .unwrap_or_else(|| format!("??"))
         }));
@@ -0,0 +1,339 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use inotify::{EventMask, EventOwned, Inotify, WatchDescriptor, WatchMask, Watches};
+use tokio::{
+    select,
+    task::spawn_blocking,
+    time::{sleep_until, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use walkdir::WalkDir;
+
+use crate::{
+    config,
+    util::{absolute, join_handle_wait_take},
+};
+
+use super::{cache, cache_file, shared::SharedCache, FilerResult};
+
+/// How long the roots must have been quiet before the changes are written to the cache.
+const DEBOUNCE: Duration = Duration::from_secs(5);
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+static LIVE: AtomicBool = AtomicBool::new(false);
+
+/// A directory relative to a root.
+type Dir = (usize, PathBuf);
+
+/// Whether changes to the roots are currently being applied to the cache as they happen.
+pub fn is_live() -> bool {
+    LIVE.load(Ordering::Relaxed)
+}
+
+/// Watch all roots for files being added, removed or renamed, and update the cache
+/// accordingly. Does nothing unless it is enabled in the config.
+pub async fn watcher_actor(cache: SharedCache, canceltoken: CancellationToken) {
+    if !config::watch_files() {
+        log::debug!("Not watching the roots since it is not configured");
+        return;
+    }
+
+    let res = watch(&cache, &canceltoken).await;
+    LIVE.store(false, Ordering::Relaxed);
+    match res {
+        Ok(()) => log::info!("Stopped watching the roots"),
+        Err(e) => log::error!("Stopped watching the roots because of: {e:?}"),
+    }
+}
+
+async fn watch(
+    cache: &SharedCache,
+    canceltoken: &CancellationToken,
+) -> anyhow::Result<()> {
+    let mut stream = Inotify::init()
+        .context("initializing inotify")?
+        .into_event_stream([0; EVENT_BUFFER_SIZE])
+        .context("creating the inotify event stream")?;
+    let mut watcher = Watcher::new(stream.watches(), cache.clone());
+
+    for root in 0..config::root_dirs().len() {
+        watcher.watch_recursive(root, "/".into()).await;
+    }
+    log::info!("Watching {} directories for changes", watcher.dirs.len());
+    LIVE.store(true, Ordering::Relaxed);
+
+    let mut deadline = None;
+    loop {
+        select! {
+            _ = canceltoken.cancelled() => break,
+            event = stream.next() => match event {
+                Some(Ok(event)) => {
+                    watcher.handle_event(event).await;
+                    if watcher.has_changes() {
+                        deadline = Some(Instant::now() + DEBOUNCE);
+                    }
+                }
+                Some(Err(e)) => return Err(e).context("reading inotify events"),
+                None => break,
+            },
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                deadline = None;
+                watcher.flush().await;
+            }
+        }
+    }
+
+    watcher.flush().await;
+    Ok(())
+}
+
+struct Watcher {
+    watches: Watches,
+    cache: SharedCache,
+    dirs: HashMap<WatchDescriptor, Dir>,
+    /// Roots to rescan completely, since some of the changes in them were missed.
+    overflowed: BTreeSet<usize>,
+    /// Directories whose direct children have changed since the last flush.
+    dirty: BTreeSet<Dir>,
+    /// Files that have been written to or moved in since the last flush, relative to a
+    /// root. What was found in them before might be wrong.
+    written: BTreeSet<Dir>,
+}
+
+impl Watcher {
+    fn new(watches: Watches, cache: SharedCache) -> Self {
+        Self {
+            watches,
+            cache,
+            dirs: HashMap::new(),
+            overflowed: BTreeSet::new(),
+            dirty: BTreeSet::new(),
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// Start watching `path` in `root` and all directories below it.
+    async fn watch_recursive(&mut self, root: usize, path: PathBuf) {
+        let abs_path = absolute_path(root, &path);
+        let dirs =
+            join_handle_wait_take(spawn_blocking(move || list_dirs(&abs_path))).await;
+
+        for sub in dirs {
+            let rel_path = match sub.as_os_str().is_empty() {
+                true => path.clone(),
+                false => path.join(sub),
+            };
+            let abs_path = absolute_path(root, &rel_path);
+            match self.watches.add(&abs_path, watch_mask()) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, (root, rel_path));
+                }
+                Err(e) => log::warn!("Could not watch {abs_path:?}: {e}"),
+            }
+        }
+    }
+
+    /// Stop watching `path` in `root` and all directories below it.
+    fn unwatch_recursive(&mut self, root: usize, path: &Path) {
+        let removed: Vec<WatchDescriptor> = self
+            .dirs
+            .iter()
+            .filter(|(_, dir)| is_at_or_below(dir, &(root, path.to_owned())))
+            .map(|(wd, _)| wd.clone())
+            .collect();
+
+        for wd in removed {
+            self.dirs.remove(&wd);
+            // NOTE: the watch is already gone if the directory was deleted
+            let _ = self.watches.remove(wd);
+        }
+    }
+
+    async fn handle_event(&mut self, event: EventOwned) {
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            log::warn!("Missed some inotify events, will rescan all roots");
+            self.overflowed.extend(0..config::root_dirs().len());
+            return;
+        }
+
+        if event.mask.contains(EventMask::IGNORED) {
+            self.dirs.remove(&event.wd);
+            return;
+        }
+
+        let Some((root, dir)) = self.dirs.get(&event.wd).cloned() else {
+            return;
+        };
+        log::debug!("Change in root={root}, dir={dir:?}: {:?}", event.mask);
+
+        match event.name {
+            Some(name) if event.mask.contains(EventMask::ISDIR) => {
+                self.directory_changed(event.mask, root, dir.join(name))
+                    .await;
+            }
+            // NOTE: a file that is copied in is created before it is written, so it has
+            // to be scanned again once it is done
+            Some(name)
+                if event
+                    .mask
+                    .intersects(EventMask::CLOSE_WRITE | EventMask::MOVED_TO) =>
+            {
+                self.written.insert((root, dir.join(name)));
+            }
+            _ => (),
+        }
+
+        self.dirty.insert((root, dir));
+    }
+
+    async fn directory_changed(&mut self, mask: EventMask, root: usize, path: PathBuf) {
+        if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+            self.watch_recursive(root, path).await;
+        } else if mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM) {
+            self.unwatch_recursive(root, &path);
+        }
+    }
+
+    fn has_changes(&self) -> bool {
+        !self.overflowed.is_empty() || !self.dirty.is_empty()
+    }
+
+    /// Rescan all dirty directories and update the cache with the result. The changes
+    /// are kept to be tried again on the next flush if it fails.
+    async fn flush(&mut self) {
+        if !self.has_changes() {
+            return;
+        }
+
+        log::info!(
+            "Applying changes in {} directories and {} roots to the cache",
+            self.dirty.len(),
+            self.overflowed.len()
+        );
+        if let Err(e) =
+            apply_changes(&self.cache, &self.overflowed, &self.dirty, &self.written).await
+        {
+            log::error!("Failed to apply changes to the cache: {e:?}");
+            return;
+        }
+        self.overflowed.clear();
+        self.dirty.clear();
+        self.written.clear();
+    }
+}
+
+async fn apply_changes(
+    cache: &SharedCache,
+    overflowed: &BTreeSet<usize>,
+    dirty: &BTreeSet<Dir>,
+    written: &BTreeSet<Dir>,
+) -> FilerResult<()> {
+    let current = cache.get();
+    if current.roots_path().is_empty() {
+        log::debug!("There is no cache to apply the changes to");
+        return Ok(());
+    }
+    // NOTE: the roots are numbered as in the config, which only matches the cache if it
+    // is up to date
+    if current.is_outdated(config::root_dirs(), &config::filters()) {
+        log::warn!("Not applying the changes since the cache is outdated, refresh it");
+        return Ok(());
+    }
+
+    cache
+        .update(|mut cache| async move {
+            for &root in overflowed {
+                cache = cache::refresh_subtree(cache, root, OsStr::new("/")).await?;
+            }
+
+            // NOTE: directories that are new since the cache was made are rescanned from
+            // their closest parent that is in the cache, which scans them completely
+            let dirty: BTreeSet<Dir> = dirty
+                .iter()
+                .filter(|(root, _)| !overflowed.contains(root))
+                .filter_map(|(root, dir)| {
+                    let mut dir = dir.as_path();
+                    loop {
+                        if cache.find_dir(*root, dir.as_os_str()).is_some() {
+                            break Some((*root, dir.to_owned()));
+                        }
+                        dir = dir.parent()?;
+                    }
+                })
+                .collect();
+
+            for (root, dir) in dirty {
+                // NOTE: it is gone if it was removed when its parent was rescanned
+                if cache.find_dir(root, dir.as_os_str()).is_some() {
+                    cache = cache::refresh_dir(cache, root, dir.as_os_str()).await?;
+                }
+            }
+            for (root, file) in written {
+                if let Some(i) = cache.find_file(*root, file.as_os_str()) {
+                    cache.forget_media(i);
+                }
+            }
+            cache::write_cache(&cache_file(), cache).await
+        })
+        .await?;
+    Ok(())
+}
+
+fn is_at_or_below((root, path): &Dir, (anc_root, anc_path): &Dir) -> bool {
+    root == anc_root && path.starts_with(anc_path)
+}
+
+fn absolute_path(root: usize, path: &Path) -> PathBuf {
+    absolute(&config::root_dirs()[root], path.as_os_str()).into()
+}
+
+fn watch_mask() -> WatchMask {
+    WatchMask::CREATE
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::DELETE
+        | WatchMask::MOVE
+        | WatchMask::ONLYDIR
+}
+
+/// All directories at and below `path`, relative to `path` (which itself is "").
+fn list_dirs(path: &Path) -> Vec<PathBuf> {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|de| match de {
+            Ok(de) if de.file_type().is_dir() => Some(de),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Could not walk: {e}");
+                None
+            }
+        })
+        .filter_map(|de| de.path().strip_prefix(path).ok().map(Path::to_owned))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dir(root: usize, path: &str) -> Dir {
+        (root, path.into())
+    }
+
+    #[test]
+    fn at_or_below() {
+        assert!(is_at_or_below(&dir(0, "/a"), &dir(0, "/a")));
+        assert!(is_at_or_below(&dir(0, "/a/b"), &dir(0, "/a")));
+        assert!(is_at_or_below(&dir(0, "/a/b"), &dir(0, "/")));
+        assert!(!is_at_or_below(&dir(0, "/a b"), &dir(0, "/a")));
+        assert!(!is_at_or_below(&dir(0, "/ab/c"), &dir(0, "/a")));
+        assert!(!is_at_or_below(&dir(1, "/a/b"), &dir(0, "/a")));
+    }
+}
@@ -6,8 +6,11 @@ mod connections;
 mod filer;
 mod mpv;
 mod process;
+mod queue;
 mod signal;
 mod state_machine;
+mod watch_later;
+mod watched;
 
 use std::process::ExitCode;
 
@@ -16,7 +19,11 @@ use tokio::{join, select, spawn, sync::mpsc, task::JoinError};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    caster::caster_actor, connections::connections_actor, signal::signal_received,
+    caster::caster_actor,
+    connections::connections_actor,
+    filer::{prober::prober_actor, shared::SharedCache, watcher::watcher_actor},
+    mpv::thumbnail::thumbnailer_actor,
+    signal::signal_received,
 };
 
 const CHANNEL_SIZE: usize = 1024;
@@ -119,14 +126,23 @@ async fn maybe_refresh_cache() {
 #[tokio::main]
 async fn async_main() -> ExitCode {
     maybe_refresh_cache().await;
+    let cache = SharedCache::load().await;
 
     let (to_cast, from_conn) = mpsc::channel(CHANNEL_SIZE);
     let (to_conn, from_cast) = mpsc::channel(CHANNEL_SIZE);
+    let (to_thumbnailer, from_caster) = mpsc::channel(CHANNEL_SIZE);
     let canceltoken = CancellationToken::new();
 
+    let thumbnailer = spawn(thumbnailer_actor(
+        from_caster,
+        to_cast.clone(),
+        canceltoken.child_token(),
+    ));
     let caster = maybe_done(spawn(caster_actor(
         to_cast,
         from_cast,
+        to_thumbnailer,
+        cache.clone(),
         canceltoken.child_token(),
     )));
     let connections = maybe_done(spawn(connections_actor(
@@ -134,6 +150,8 @@ async fn async_main() -> ExitCode {
         from_conn,
         canceltoken.child_token(),
     )));
+    let prober = spawn(prober_actor(cache.clone(), canceltoken.child_token()));
+    let watcher = spawn(watcher_actor(cache, canceltoken.child_token()));
     tokio::pin!(caster);
     tokio::pin!(connections);
 
@@ -147,6 +165,15 @@ async fn async_main() -> ExitCode {
     canceltoken.cancel();
 
     join!(&mut caster, &mut connections);
+    if let Err(je) = watcher.await {
+        log::error!("Actor 'watcher' join error: {}", je);
+    }
+    if let Err(je) = prober.await {
+        log::error!("Actor 'prober' join error: {}", je);
+    }
+    if let Err(je) = thumbnailer.await {
+        log::error!("Actor 'thumbnailer' join error: {}", je);
+    }
     match (
         caster.take_output().expect("value not taken"),
         connections.take_output().expect("value not taken"),
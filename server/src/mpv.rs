@@ -1,31 +1,70 @@
 use anyhow::Context;
 use libmpv::{EndReason, Event, LogLevel, PropertyValue};
-use std::{mem, time::Duration};
+use std::{ffi::OsStr, mem, time::Duration};
 
 use protocol::{
     to_client::front::mpv::{
-        playstate::{PlayState, Track as ClientTrack},
+        playstate::{
+            AbLoop, Chapter as ClientChapter, PlayState, Segment, Track as ClientTrack,
+            Tweaks,
+        },
         Mpv as ClientMpv,
     },
-    to_server::mpvcontrol::MpvControl,
-    util::{Percent, Positive},
+    to_server::{mpvcontrol::MpvControl, mpvstart::options::MpvOptions},
+    util::{Normal, Percent, Positive},
 };
 use tokio::task::spawn_blocking;
 
-use crate::{config, mpv::lang::HumanLang, util::join_handle_wait_take};
+use crate::{
+    config::{self, Languages, YtdlPolicy},
+    util::join_handle_wait_take,
+};
 
-use self::lang::{AutoLang, Lang};
+use self::{
+    lang::{AutoLang, Lang},
+    seek::{Seek, Seeker},
+    sidecar::Sidecar,
+    skip::Skipper,
+};
 
+mod headless;
 mod lang;
+pub mod probe;
+mod seek;
+pub mod sidecar;
+pub mod skip;
+pub mod thumbnail;
+mod tweaks;
+pub mod ytdl;
 
 pub type MpvResult<T> = anyhow::Result<T>;
 
 const DEF_USR: u64 = 0;
+/// The userdata of seek commands, to know when they are done
+const SEEK_USR: u64 = 1;
+/// The userdata of the commands that add subtitle files
+const SUB_ADD_USR: u64 = 2;
 
 pub struct MpvHandle {
     handle: libmpv::Handle<libmpv::Async>,
     state: MpvState,
     auto_lang: AutoLang,
+    seeker: Seeker,
+    /// Subtitle files to add when the file has been loaded
+    sidecars: Vec<Sidecar>,
+    /// How many of the subtitle files are still being added
+    adding_sidecars: usize,
+    skipper: Skipper,
+}
+
+/// How to play something, besides what the client asked for in [`MpvOptions`]
+pub struct Setup {
+    pub languages: Languages,
+    /// How to choose the format of URLs, `None` for local files
+    pub ytdl: Option<YtdlPolicy>,
+    /// Subtitle files to add to the embedded ones
+    pub sidecars: Vec<Sidecar>,
+    pub skipper: Skipper,
 }
 
 #[derive(Debug, Clone)]
@@ -38,7 +77,21 @@ struct State {
     muted: bool,
     chapters: i64,
     chapter: i64,
+    chapter_list: Vec<Chapter>,
     tracks: Vec<Track>,
+    speed: f64,
+    audio_delay: f64,
+    /// As a power of 2
+    zoom: f64,
+    pan_x: f64,
+    pan_y: f64,
+    aspect: f64,
+    deinterlace: bool,
+    ab_loop_a: Option<f64>,
+    ab_loop_b: Option<f64>,
+    loop_file: bool,
+    /// The part being played that can be skipped, and where it ends
+    skip: Option<(Segment, f64)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,8 +102,14 @@ struct Track {
     selected: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum TrackType {
+#[derive(Debug, Clone, PartialEq)]
+struct Chapter {
+    title: Option<String>,
+    time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TrackType {
     Audio,
     Video,
     Sub,
@@ -59,7 +118,7 @@ enum TrackType {
 #[derive(Debug)]
 enum MpvState {
     Load,
-    Play(State),
+    Play(Box<State>),
     End(EndReason),
 }
 
@@ -80,7 +139,19 @@ impl Default for State {
             muted: false,
             chapters: 0,
             chapter: 0,
+            chapter_list: Vec::new(),
             tracks: Vec::new(),
+            speed: 1.0,
+            audio_delay: 0.0,
+            zoom: 0.0,
+            pan_x: 0.0,
+            pan_y: 0.0,
+            aspect: -1.0,
+            deinterlace: false,
+            ab_loop_a: None,
+            ab_loop_b: None,
+            loop_file: false,
+            skip: None,
         }
     }
 }
@@ -91,6 +162,8 @@ impl MpvState {
             MpvState::Load => Some(ClientMpv::Load),
             MpvState::End(_) => None,
             MpvState::Play(state) => Some(ClientMpv::PlayState(PlayState {
+                // Set by the patcher
+                seq: 0,
                 title: state.title.clone(),
                 pause: state.pause,
                 progress: Duration::try_from_secs_f64(state.playback_time)
@@ -104,13 +177,53 @@ impl MpvState {
                 } else {
                     None
                 },
+                chapters: to_client_chapters(&state.chapter_list),
                 subtitles: to_client_tracks(&state.tracks, TrackType::Sub),
                 audios: to_client_tracks(&state.tracks, TrackType::Audio),
+                tweaks: to_client_tweaks(state),
+                skip: state.skip.as_ref().map(|(segment, _)| segment.clone()),
             })),
         }
     }
 }
 
+fn to_client_chapters(chapters: &[Chapter]) -> Vec<ClientChapter> {
+    chapters
+        .iter()
+        .enumerate()
+        .map(|(i, c)| ClientChapter {
+            title: c
+                .title
+                .clone()
+                .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+            start: c
+                .time
+                .and_then(|time| Duration::try_from_secs_f64(time).ok()),
+        })
+        .collect()
+}
+
+fn to_client_tweaks(state: &State) -> Tweaks {
+    let secs = |secs: f64| Duration::try_from_secs_f64(secs).unwrap_or(Duration::ZERO);
+    Tweaks {
+        speed: Percent::new(100.0 * state.speed).unwrap_or_default(),
+        audio_delay: (1000.0 * state.audio_delay).round() as i64,
+        zoom: Percent::new(100.0 * state.zoom.exp2()).unwrap_or_default(),
+        pan: (
+            (100.0 * state.pan_x).round() as i64,
+            (100.0 * state.pan_y).round() as i64,
+        ),
+        aspect: tweaks::aspect_name(state.aspect),
+        deinterlace: state.deinterlace,
+        ab_loop: match (state.ab_loop_a, state.ab_loop_b) {
+            (Some(a), Some(b)) => AbLoop::AB(secs(a), secs(b)),
+            (Some(a), None) => AbLoop::A(secs(a)),
+            (None, _) => AbLoop::Off,
+        },
+        loop_file: state.loop_file,
+    }
+}
+
 fn to_client_tracks(tracks: &[Track], ttype: TrackType) -> Vec<ClientTrack> {
     tracks
         .iter()
@@ -125,12 +238,15 @@ fn to_client_tracks(tracks: &[Track], ttype: TrackType) -> Vec<ClientTrack> {
 
 impl MpvHandle {
     pub fn command(&mut self, cmd: MpvControl) -> MpvResult<()> {
-        let short = Duration::from_secs(5);
-        let long = Duration::from_secs(30);
+        let short = 5;
+        let long = 30;
         let vol = 2.0;
         let delay = 0.1;
         let scale = 0.1;
         let pos = 1.0;
+        let speed = 0.1;
+        let zoom = 0.1;
+        let pan = 0.05;
 
         match cmd {
             MpvControl::TogglePause => self.handle.toggle_pause().asynch(DEF_USR)?,
@@ -146,14 +262,11 @@ impl MpvHandle {
             }
             MpvControl::NextChapter => self.handle.add_chapter(1).asynch(DEF_USR)?,
             MpvControl::PrevChapter => self.handle.add_chapter(-1).asynch(DEF_USR)?,
-            MpvControl::SeekBack => self.handle.seek_backward(short).asynch(DEF_USR)?,
-            MpvControl::SeekForward => self.handle.seek_forward(short).asynch(DEF_USR)?,
-            MpvControl::SeekBackLong => {
-                self.handle.seek_backward(long).asynch(DEF_USR)?
-            }
-            MpvControl::SeekForwardLong => {
-                self.handle.seek_forward(long).asynch(DEF_USR)?
-            }
+            MpvControl::SetChapter(i) => self.handle.set_chapter(i).asynch(DEF_USR)?,
+            MpvControl::SeekBack => self.seek(Seek::Relative(-short))?,
+            MpvControl::SeekForward => self.seek(Seek::Relative(short))?,
+            MpvControl::SeekBackLong => self.seek(Seek::Relative(-long))?,
+            MpvControl::SeekForwardLong => self.seek(Seek::Relative(long))?,
             MpvControl::SetSub(id) => self.handle.set_sub(id).asynch(DEF_USR)?,
             MpvControl::SubLarger => self.handle.add_sub_scale(scale).asynch(DEF_USR)?,
             MpvControl::SubSmaller => {
@@ -161,17 +274,81 @@ impl MpvHandle {
             }
             MpvControl::SubMoveUp => self.handle.add_sub_pos(-pos).asynch(DEF_USR)?,
             MpvControl::SubMoveDown => self.handle.add_sub_pos(pos).asynch(DEF_USR)?,
-            // TODO: probably add a safeguard to ignore new seek commands while another
-            // seek is in progress
-            MpvControl::SeekAbs(perc) => self
-                .handle
-                .seek_abs_percent(perc.as_f64())
-                .asynch(DEF_USR)?,
+            MpvControl::SeekAbs(perc) => self.seek(Seek::Absolute(perc))?,
+            MpvControl::SpeedUp => self.handle.add_speed(speed).asynch(DEF_USR)?,
+            MpvControl::SpeedDown => self.handle.add_speed(-speed).asynch(DEF_USR)?,
+            MpvControl::SpeedReset => self.handle.set_speed(1.0).asynch(DEF_USR)?,
+            MpvControl::AudioDelayEarlier => {
+                self.handle.add_audio_delay(-delay).asynch(DEF_USR)?
+            }
+            MpvControl::AudioDelayLater => {
+                self.handle.add_audio_delay(delay).asynch(DEF_USR)?
+            }
+            MpvControl::ZoomIn => self.handle.add_video_zoom(zoom).asynch(DEF_USR)?,
+            MpvControl::ZoomOut => self.handle.add_video_zoom(-zoom).asynch(DEF_USR)?,
+            MpvControl::PanLeft => self.handle.add_video_pan_x(-pan).asynch(DEF_USR)?,
+            MpvControl::PanRight => self.handle.add_video_pan_x(pan).asynch(DEF_USR)?,
+            MpvControl::PanUp => self.handle.add_video_pan_y(-pan).asynch(DEF_USR)?,
+            MpvControl::PanDown => self.handle.add_video_pan_y(pan).asynch(DEF_USR)?,
+            MpvControl::CycleAspect => {
+                let aspect = self.play_state().map_or(-1.0, |s| s.aspect);
+                self.handle
+                    .set_video_aspect_override(tweaks::next_aspect(aspect))
+                    .asynch(DEF_USR)?
+            }
+            MpvControl::ResetVideo => {
+                self.handle.set_video_zoom(0.0).asynch(DEF_USR)?;
+                self.handle.set_video_pan_x(0.0).asynch(DEF_USR)?;
+                self.handle.set_video_pan_y(0.0).asynch(DEF_USR)?;
+                self.handle
+                    .set_video_aspect_override(-1.0)
+                    .asynch(DEF_USR)?
+            }
+            MpvControl::ToggleDeinterlace => {
+                self.handle.toggle_deinterlace().asynch(DEF_USR)?
+            }
+            MpvControl::AbLoop => self.handle.ab_loop().asynch(DEF_USR)?,
+            MpvControl::SkipSegment => {
+                if let Some(end) = self.play_state().and_then(skip_target) {
+                    self.seek(end)?
+                }
+            }
+            MpvControl::ToggleLoopFile => {
+                let looping = self.play_state().is_some_and(|s| s.loop_file);
+                self.handle
+                    .set_loop_file(if looping { "no" } else { "inf" })
+                    .asynch(DEF_USR)?
+            }
         };
 
         Ok(())
     }
 
+    fn play_state(&self) -> Option<&State> {
+        match &self.state {
+            MpvState::Play(state) => Some(state),
+            MpvState::Load | MpvState::End(_) => None,
+        }
+    }
+
+    /// Seek now, or as soon as the seek in progress is done
+    fn seek(&mut self, seek: Seek) -> MpvResult<()> {
+        match self.seeker.request(seek) {
+            Some(seek) => {
+                let res = send_seek(&mut self.handle, seek);
+                if res.is_err() {
+                    // NOTE: there will be no reply, so don't wait for one
+                    self.seeker = Seeker::default();
+                }
+                res
+            }
+            None => {
+                log::debug!("Delaying {seek:?} until the seek in progress is done");
+                Ok(())
+            }
+        }
+    }
+
     pub async fn next(&mut self) -> Option<MpvResult<ClientMpv>> {
         if let MpvState::End(_) = self.state {
             return None;
@@ -199,7 +376,13 @@ impl MpvHandle {
                 Event::QueueOverflow => log::error!("Mpv queue overflow"),
                 Event::PropertyChange(propvalue) => {
                     if let MpvState::Play(play) = &mut self.state {
-                        let updated = play.update_state(&propvalue);
+                        let mut updated = play.update_state(&propvalue);
+                        let skip = self.skipper.skippable(play);
+                        updated |= replace(&mut play.skip, skip.clone());
+                        // NOTE: the duration might not be known yet, so wait for the
+                        // target before counting it as skipped
+                        let auto_skip = skip_target(play)
+                            .filter(|_| self.skipper.auto_skip(skip.as_ref()).is_some());
 
                         if self.auto_lang.has_not_chosen()
                             && matches!(propvalue, PropertyValue::TrackList(_))
@@ -213,6 +396,13 @@ impl MpvHandle {
                             }
                         }
 
+                        if let Some(target) = auto_skip {
+                            log::info!("Skipping {skip:?} automatically");
+                            if let Err(e) = self.seek(target) {
+                                break Some(Err(e));
+                            }
+                        }
+
                         if updated {
                             break Some(Ok(self
                                 .state
@@ -228,14 +418,18 @@ impl MpvHandle {
                 }
                 Event::StartFile => (),
                 Event::FileLoaded => {
-                    // TODO: how to avoid sending many state updates in rapid succession
-                    // after all properties come in one after another?
-                    if let Err(e) = observe_properties(&mut self.handle)
-                        .context("observing properties")
-                    {
-                        break Some(Err(e));
+                    // NOTE: the properties are observed once the subtitle files are added,
+                    // so that they are in the track list that the languages are chosen from
+                    self.adding_sidecars =
+                        add_sidecars(&mut self.handle, mem::take(&mut self.sidecars));
+                    if self.adding_sidecars == 0 {
+                        if let Err(e) = observe_properties(&mut self.handle)
+                            .context("observing properties")
+                        {
+                            break Some(Err(e));
+                        }
                     }
-                    self.state = MpvState::Play(State::default());
+                    self.state = MpvState::Play(Box::default());
                     break Some(Ok(self.state.to_client_state().expect("is not end")));
                 }
                 Event::EndFile { reason, error } => {
@@ -264,7 +458,28 @@ impl MpvHandle {
                         break Some(Err(anyhow::anyhow!("mpv set property error: userdata={userdata}, error='{error}'")));
                     }
                 }
+                Event::Command { error, userdata } if userdata == SUB_ADD_USR => {
+                    if let Some(error) = error {
+                        log::warn!("Failed to add a subtitle file: {error}");
+                    }
+                    self.adding_sidecars = self.adding_sidecars.saturating_sub(1);
+                    if self.adding_sidecars == 0 {
+                        if let Err(e) = observe_properties(&mut self.handle)
+                            .context("observing properties")
+                        {
+                            break Some(Err(e));
+                        }
+                    }
+                }
                 Event::Command { error, userdata } => {
+                    if userdata == SEEK_USR {
+                        if let Some(next) = self.seeker.done() {
+                            if let Err(e) = send_seek(&mut self.handle, next) {
+                                break Some(Err(e));
+                            }
+                        }
+                    }
+
                     if let Some(error) = error {
                         break Some(Err(anyhow::anyhow!(
                             "mpv command error: userdata={userdata}, error='{error}'"
@@ -291,11 +506,16 @@ impl MpvHandle {
     }
 }
 
-// TODO: the ytdl-format property is not set anymore to limit the resolution of videos
-//   ytdl-format="bestvideo[height<=1080]+bestaudio/best[height<=1080]/bestvideo+bestaudio/best"
-// Maybe write a maximum height as a config value in the config file and use it here?
-// TODO: create a MpvOptions instead of having multiple arguments?
-pub fn mpv(path: &str, paused: bool) -> MpvResult<MpvHandle> {
+/// Start playing `path`. The languages and ytdl policy in `setup` are overridden by
+/// what is set in `options`.
+pub fn mpv(path: &OsStr, options: &MpvOptions, setup: Setup) -> MpvResult<MpvHandle> {
+    let Setup {
+        languages,
+        ytdl,
+        sidecars,
+        skipper,
+    } = setup;
+
     let mut mpv = libmpv::Handle::new().context("creating handle")?;
 
     mpv.request_log_messages(libmpv::LogLevel::Info)
@@ -312,10 +532,48 @@ pub fn mpv(path: &str, paused: bool) -> MpvResult<MpvHandle> {
     let version = mpv.version().synch().context("getting version")?;
     log::debug!("mpv version: {version}");
 
-    mpv.set_paused(paused)
+    mpv.set_paused(options.paused)
         .asynch(DEF_USR)
         .context("setting paused")?;
 
+    if let Some(start) = options.start.filter(|start| !start.is_zero()) {
+        mpv.set_start(format!("{:.3}", start.as_secs_f64()))
+            .asynch(DEF_USR)
+            .context("setting start position")?;
+    }
+
+    if let Some(volume) = options.volume {
+        mpv.set_volume(volume.as_f64())
+            .asynch(DEF_USR)
+            .context("setting volume")?;
+    }
+
+    // NOTE: the subtitle files next to the video are added as sidecars instead, mpv would
+    // add the ones with the same name twice
+    mpv.set_sub_auto("no")
+        .asynch(DEF_USR)
+        .context("disabling sub-auto")?;
+
+    if options.audio_only {
+        mpv.set_video("no")
+            .asynch(DEF_USR)
+            .context("disabling video")?;
+    }
+
+    if options.loop_file {
+        mpv.set_loop_file("inf")
+            .asynch(DEF_USR)
+            .context("setting loop file")?;
+    }
+
+    if let Some(policy) = ytdl {
+        let format = ytdl::format(&policy);
+        log::debug!("Using ytdl-format: {format}");
+        mpv.set_ytdl_format(format)
+            .asynch(DEF_USR)
+            .context("setting the ytdl format")?;
+    }
+
     mpv.loadfile(path)
         .asynch(DEF_USR)
         .context("loading the file")?;
@@ -327,11 +585,65 @@ pub fn mpv(path: &str, paused: bool) -> MpvResult<MpvHandle> {
     Ok(MpvHandle {
         handle: mpv.into_async(),
         state: MpvState::Load,
-        // TODO: make the languages configurable
-        auto_lang: AutoLang::new(HumanLang::English, HumanLang::Japanese),
+        auto_lang: AutoLang::new(preferred_languages(languages, options)),
+        seeker: Seeker::default(),
+        sidecars,
+        adding_sidecars: 0,
+        skipper,
     })
 }
 
+/// Where to seek to skip the part being played
+fn skip_target(state: &State) -> Option<Seek> {
+    let (_, end) = state.skip.as_ref()?;
+    Percent::<Normal>::of(*end, state.duration).map(Seek::Absolute)
+}
+
+/// Starts adding the subtitle files to the tracks, and gives how many of them there will
+/// be a reply for.
+fn add_sidecars(
+    mpv: &mut libmpv::Handle<libmpv::Async>,
+    sidecars: Vec<Sidecar>,
+) -> usize {
+    sidecars
+        .into_iter()
+        .filter(|sidecar| {
+            log::info!("Adding subtitle file: {sidecar:?}");
+            let res = mpv
+                .sub_add(
+                    sidecar.path.as_os_str(),
+                    sidecar.title.as_deref(),
+                    sidecar.lang.as_deref(),
+                )
+                .asynch(SUB_ADD_USR);
+            if let Err(e) = &res {
+                log::warn!("Failed to add the subtitle file {:?}: {e}", sidecar.path);
+            }
+            res.is_ok()
+        })
+        .count()
+}
+
+fn send_seek(mpv: &mut libmpv::Handle<libmpv::Async>, seek: Seek) -> MpvResult<()> {
+    let secs = |secs: i64| Duration::from_secs(secs.unsigned_abs());
+    match seek {
+        Seek::Absolute(perc) => mpv.seek_abs_percent(perc.as_f64()).asynch(SEEK_USR),
+        Seek::Relative(s) if s < 0 => mpv.seek_backward(secs(s)).asynch(SEEK_USR),
+        Seek::Relative(s) => mpv.seek_forward(secs(s)).asynch(SEEK_USR),
+    }
+    .with_context(|| format!("seeking {seek:?}"))
+}
+
+fn preferred_languages(mut languages: Languages, options: &MpvOptions) -> Languages {
+    if !options.sub_langs.is_empty() {
+        languages.sub.langs = options.sub_langs.clone();
+    }
+    if !options.audio_langs.is_empty() {
+        languages.audio.langs = options.audio_langs.clone();
+    }
+    languages
+}
+
 fn observe_properties(mpv: &mut libmpv::Handle<libmpv::Async>) -> MpvResult<()> {
     mpv.observe_media_title().context("observe media title")?;
     mpv.observe_paused().context("observe paused")?;
@@ -342,7 +654,19 @@ fn observe_properties(mpv: &mut libmpv::Handle<libmpv::Async>) -> MpvResult<()>
     mpv.observe_muted().context("observe muted")?;
     mpv.observe_chapter().context("observe chapter")?;
     mpv.observe_chapters().context("observe chapters")?;
+    mpv.observe_chapter_list().context("observe chapter list")?;
     mpv.observe_track_list().context("observe track list")?;
+    mpv.observe_speed().context("observe speed")?;
+    mpv.observe_audio_delay().context("observe audio delay")?;
+    mpv.observe_video_zoom().context("observe video zoom")?;
+    mpv.observe_video_pan_x().context("observe video pan x")?;
+    mpv.observe_video_pan_y().context("observe video pan y")?;
+    mpv.observe_video_aspect_override()
+        .context("observe video aspect override")?;
+    mpv.observe_deinterlace().context("observe deinterlace")?;
+    mpv.observe_ab_loop_a().context("observe ab loop a")?;
+    mpv.observe_ab_loop_b().context("observe ab loop b")?;
+    mpv.observe_loop_file().context("observe loop file")?;
     Ok(())
 }
 
@@ -373,9 +697,28 @@ impl State {
             PropertyValue::Mute(new) => replace(&mut self.muted, *new),
             PropertyValue::Chapters(new) => replace(&mut self.chapters, *new),
             PropertyValue::Chapter(new) => replace(&mut self.chapter, *new),
+            PropertyValue::ChapterList(new) => {
+                replace(&mut self.chapter_list, node_to_chapters(new))
+            }
             PropertyValue::TrackList(new) => {
                 replace(&mut self.tracks, node_to_tracks(new))
             }
+            PropertyValue::Speed(new) => replace(&mut self.speed, *new),
+            PropertyValue::AudioDelay(new) => replace(&mut self.audio_delay, *new),
+            PropertyValue::VideoZoom(new) => replace(&mut self.zoom, *new),
+            PropertyValue::VideoPanX(new) => replace(&mut self.pan_x, *new),
+            PropertyValue::VideoPanY(new) => replace(&mut self.pan_y, *new),
+            PropertyValue::VideoAspectOverride(new) => replace(&mut self.aspect, *new),
+            PropertyValue::Deinterlace(new) => replace(&mut self.deinterlace, *new),
+            PropertyValue::AbLoopA(new) => {
+                replace(&mut self.ab_loop_a, tweaks::loop_point(new))
+            }
+            PropertyValue::AbLoopB(new) => {
+                replace(&mut self.ab_loop_b, tweaks::loop_point(new))
+            }
+            PropertyValue::LoopFile(new) => {
+                replace(&mut self.loop_file, tweaks::is_looping(new))
+            }
             _ => false,
         }
     }
@@ -395,6 +738,29 @@ fn mpv_log_level_convert(level: LogLevel) -> Option<log::Level> {
     }
 }
 
+fn node_to_chapters(node: &libmpv::Node) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+
+    if let libmpv::Node::Array(nodes) = node {
+        for node in nodes {
+            if let libmpv::Node::Map(map) = node {
+                // Kept even without a time, the index is the one mpv uses for the chapter
+                let time = map.get("time").and_then(|time| time.try_to_double());
+                let title = map
+                    .get("title")
+                    .and_then(|title| title.try_to_string())
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string);
+
+                chapters.push(Chapter { title, time });
+            }
+        }
+    }
+
+    chapters
+}
+
 fn node_to_tracks(node: &libmpv::Node) -> Vec<Track> {
     let mut tracks = Vec::new();
 
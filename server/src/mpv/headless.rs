@@ -0,0 +1,82 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use libmpv::{EndReason, Event};
+use tokio_util::sync::CancellationToken;
+
+use super::{node_to_tracks, TrackType};
+
+/// How often the waiting checks if it should give up
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Load `video` into an mpv that uses `vo` and doesn't play any sound. `setup` is called
+/// right before the file is loaded. Gives `None` if the file could not be loaded.
+pub fn open<F>(
+    video: &Path,
+    vo: &str,
+    deadline: Instant,
+    canceltoken: &CancellationToken,
+    setup: F,
+) -> anyhow::Result<Option<libmpv::Handle<libmpv::Sync>>>
+where
+    F: FnOnce(&mut libmpv::Handle<libmpv::Sync>) -> libmpv::Result<()>,
+{
+    let mut mpv = libmpv::Handle::new()
+        .context("creating handle")?
+        .init()
+        .context("initializing")?;
+
+    mpv.set_vo(vo).synch().context("setting vo")?;
+    mpv.set_ao("null").synch().context("setting ao")?;
+    mpv.set_untimed(true).synch().context("setting untimed")?;
+    mpv.set_sub_visibility(false)
+        .synch()
+        .context("hiding the subs")?;
+    setup(&mut mpv).context("setting up")?;
+    mpv.loadfile(video).synch().context("loading the file")?;
+
+    let loaded = wait_for(&mut mpv, deadline, canceltoken, |event| match event {
+        Event::FileLoaded => Some(Ok(true)),
+        Event::EndFile {
+            reason: EndReason::Error,
+            error,
+        } => Some(Err(anyhow::anyhow!("mpv failed to load: {error:?}"))),
+        Event::EndFile { .. } | Event::Shutdown => Some(Ok(false)),
+        _ => None,
+    })?;
+    Ok(loaded.then_some(mpv))
+}
+
+/// If the loaded file has any video, which includes cover art
+pub fn has_video(mpv: &mut libmpv::Handle<libmpv::Sync>) -> anyhow::Result<bool> {
+    let tracks = mpv.track_list().synch().context("getting the tracks")?;
+    Ok(node_to_tracks(&tracks)
+        .iter()
+        .any(|t| t.ttype == TrackType::Video))
+}
+
+/// Wait until `pick` returns something for an event, or give up at `deadline`
+pub fn wait_for<T, F>(
+    mpv: &mut libmpv::Handle<libmpv::Sync>,
+    deadline: Instant,
+    canceltoken: &CancellationToken,
+    mut pick: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut(Event) -> Option<anyhow::Result<T>>,
+{
+    loop {
+        if canceltoken.is_cancelled() {
+            anyhow::bail!("cancelled");
+        }
+        if Instant::now() > deadline {
+            anyhow::bail!("timed out");
+        }
+        if let Some(res) = pick(mpv.wait_event(POLL_INTERVAL)) {
+            return res;
+        }
+    }
+}
@@ -1,9 +1,33 @@
-use std::fmt;
+use std::{cmp::Reverse, fmt};
 
 use anyhow::Context;
 
+use crate::config::{Languages, TrackPrefs};
+
 use super::{MpvResult, Track, TrackType, DEF_USR};
 
+/// Different names of the same language, that tracks can be tagged with
+const LANGUAGE_NAMES: &[&[&str]] = &[
+    &["en", "eng", "english"],
+    &["ja", "jpn", "japanese"],
+    &["sv", "swe", "swedish", "svenska"],
+    &["no", "nor", "nb", "nob", "norwegian", "norsk"],
+    &["da", "dan", "danish", "dansk"],
+    &["fi", "fin", "finnish", "suomi"],
+    &["de", "deu", "ger", "german", "deutsch"],
+    &["nl", "nld", "dut", "dutch"],
+    &["fr", "fra", "fre", "french", "français"],
+    &["es", "spa", "spanish", "español"],
+    &["it", "ita", "italian", "italiano"],
+    &["pt", "por", "portuguese", "português"],
+    &["pl", "pol", "polish", "polski"],
+    &["ru", "rus", "russian"],
+    &["zh", "zho", "chi", "chinese"],
+    &["ko", "kor", "korean"],
+    &["ar", "ara", "arabic"],
+    &["hi", "hin", "hindi"],
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Lang {
     title: Option<String>,
@@ -34,19 +58,45 @@ impl Lang {
         Matcher::new(self.lang.as_deref())
     }
 
-    fn title(&self) -> Matcher<'_> {
-        Matcher::new(self.title.as_deref())
-    }
-
     fn ilang(&self) -> Matcher<'_> {
         self.lang().case_insensitive()
     }
 
-    fn ititle(&self) -> Matcher<'_> {
-        self.title().case_insensitive()
+    /// If the track is in the language called `name`, under any of its names
+    pub(super) fn is_language(&self, name: &str) -> bool {
+        match (&self.lang, language_group(name)) {
+            (None, _) => false,
+            (Some(lang), Some(group)) => language_group(lang) == Some(group),
+            (Some(_), None) => self.ilang().equals(name),
+        }
+    }
+
+    /// If the title contains the words in `keyword`, which is case sensitive only if it
+    /// has upper case letters
+    fn title_contains(&self, keyword: &str) -> bool {
+        self.title
+            .as_deref()
+            .is_some_and(|t| contains_keyword(t, keyword))
     }
 }
 
+/// If `text` contains the words in `keyword`, which is case sensitive only if it has upper
+/// case letters
+pub(super) fn contains_keyword(text: &str, keyword: &str) -> bool {
+    Matcher::new(Some(text).filter(|t| !t.is_empty()))
+        .smart_case(keyword)
+        .contains_phrase(keyword)
+}
+
+/// The index in [`LANGUAGE_NAMES`] of the language called `name`, ignoring any region as
+/// in "en-US"
+pub(super) fn language_group(name: &str) -> Option<usize> {
+    let name = name.split(['-', '_']).next().unwrap_or(name).to_lowercase();
+    LANGUAGE_NAMES
+        .iter()
+        .position(|names| names.contains(&name.as_str()))
+}
+
 struct Matcher<'a> {
     inner: &'a str,
     str_cmp: fn(&str, &str) -> bool,
@@ -66,20 +116,32 @@ impl<'a> Matcher<'a> {
         self
     }
 
-    fn equals(&self, b: &str) -> bool {
-        (self.str_cmp)(self.inner, b)
+    /// Case insensitive unless `pattern` has upper case letters
+    fn smart_case(self, pattern: &str) -> Self {
+        match pattern.chars().any(char::is_uppercase) {
+            true => self,
+            false => self.case_insensitive(),
+        }
     }
 
-    fn contains(&self, word: &str) -> bool {
-        words(self.inner).any(|w| (self.str_cmp)(w, word))
+    fn equals(&self, b: &str) -> bool {
+        (self.str_cmp)(self.inner, b)
     }
 
-    fn any_equals(&self, words: impl IntoIterator<Item = impl AsRef<str>>) -> bool {
-        words.into_iter().any(|w| self.equals(w.as_ref()))
-    }
+    /// If the words in `phrase` come right after each other somewhere in here
+    fn contains_phrase(&self, phrase: &str) -> bool {
+        let phrase: Vec<_> = words(phrase).collect();
+        if phrase.is_empty() {
+            return false;
+        }
 
-    fn any_contains(&self, words: impl IntoIterator<Item = impl AsRef<str>>) -> bool {
-        words.into_iter().any(|w| self.contains(w.as_ref()))
+        let words: Vec<_> = words(self.inner).collect();
+        words.windows(phrase.len()).any(|window| {
+            window
+                .iter()
+                .zip(&phrase)
+                .all(|(w, p)| (self.str_cmp)(w, p))
+        })
     }
 }
 
@@ -116,19 +178,18 @@ impl fmt::Display for Lang {
 
 pub struct AutoLang {
     has_chosen: bool,
-    preferred_sub: HumanLang,
-    preferred_audio: HumanLang,
+    languages: Languages,
 }
 
 impl AutoLang {
-    pub fn new(sub: HumanLang, dub: HumanLang) -> Self {
+    pub fn new(languages: Languages) -> Self {
         Self {
             has_chosen: false,
-            preferred_sub: sub,
-            preferred_audio: dub,
+            languages,
         }
     }
 
+    /// Select the best ranked sub and audio tracks, if they are any good
     pub fn auto_choose(
         &mut self,
         mpv: &mut libmpv::Handle<libmpv::Async>,
@@ -138,14 +199,15 @@ impl AutoLang {
 
         log::info!("Performing automatic track selection");
 
-        if let Some(id) = Self::choose_track(tracks, TrackType::Sub, self.preferred_sub) {
+        if let Some(id) = Self::choose_track(tracks, TrackType::Sub, &self.languages.sub)
+        {
             mpv.set_sub(id)
                 .asynch(DEF_USR)
                 .context("auto setting the sub")?;
         }
 
         if let Some(id) =
-            Self::choose_track(tracks, TrackType::Audio, self.preferred_audio)
+            Self::choose_track(tracks, TrackType::Audio, &self.languages.audio)
         {
             mpv.set_audio(id)
                 .asynch(DEF_USR)
@@ -158,7 +220,7 @@ impl AutoLang {
     fn choose_track(
         tracks: &[Track],
         ttype: TrackType,
-        preferred: HumanLang,
+        prefs: &TrackPrefs,
     ) -> Option<i64> {
         let tracks: Vec<_> = tracks.iter().filter(|t| t.ttype == ttype).collect();
 
@@ -176,7 +238,7 @@ impl AutoLang {
             .unwrap_or(usize::MAX);
         log::info!("Available {type_name}: {:?} (selected={selected})", names);
 
-        let chosen = auto_choose(tracks, selected, preferred);
+        let chosen = auto_choose(tracks, selected, prefs);
         match chosen {
             Some((i, track)) => log::info!("Chose: {} ({i})", track.lang),
             None => log::info!("Chose nothing"),
@@ -190,39 +252,31 @@ impl AutoLang {
     }
 }
 
-// TODO: the english is very sub focused, and japanese contains multiple languages, so
-// this is maybe not a good way to choose which language to use. Maybe introduce another
-// enum for sub or dub?
-#[derive(Copy, Clone, Debug)]
-pub enum HumanLang {
-    English,
-    Japanese,
-}
-
+/// How good a track is, ordered from worst to best
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Copy)]
 enum Prio {
     /// Actively avoid using
     Avoid,
     /// Can use, but prefer not to
     NotUse,
-    /// Perfectly fine to use
-    Use,
-    /// Prefer using this
-    Prefer,
-    /// Such a good match that it must be used
-    Required,
+    /// In a wanted language, but with a title to avoid. Has the rank of the language.
+    Use(Reverse<usize>),
+    /// In a wanted language. Has the rank of the language, and then of the title, where
+    /// titles without any preferred keyword come last.
+    Prefer(Reverse<usize>, Reverse<usize>),
 }
 
-impl HumanLang {
-    fn choose(self, lang: &Lang) -> Prio {
-        match self {
-            Self::English => choose_eng_sub(lang),
-            Self::Japanese => choose_jap_swe_eng_dub(lang),
-        }
+impl Prio {
+    fn is_usable(self) -> bool {
+        matches!(self, Self::Use(_) | Self::Prefer(..))
     }
 }
 
-fn auto_choose<It, T>(tracks: It, selected: usize, human: HumanLang) -> Option<(usize, T)>
+fn auto_choose<It, T>(
+    tracks: It,
+    selected: usize,
+    prefs: &TrackPrefs,
+) -> Option<(usize, T)>
 where
     T: AsRef<Lang>,
     It: IntoIterator<Item = T>,
@@ -232,7 +286,7 @@ where
         .into_iter()
         .enumerate()
         .map(|(i, t)| {
-            let prio = human.choose(t.as_ref());
+            let prio = rank(prefs, t.as_ref());
             if i == selected {
                 selected_prio = prio;
             }
@@ -246,14 +300,14 @@ where
         return None;
     };
 
-    if selected_prio == Prio::Avoid || max_prio >= Prio::Use {
+    if selected_prio == Prio::Avoid || max_prio.is_usable() {
         Some((max_i, max_t))
     } else {
         None
     }
 }
 
-fn choose_eng_sub(lang: &Lang) -> Prio {
+fn rank(prefs: &TrackPrefs, lang: &Lang) -> Prio {
     // NOTE: some youtube videos have a subtitle track called "live_chat 'json'" thats
     // empty
     let is_avoid = lang.ilang().equals("live_chat");
@@ -261,41 +315,21 @@ fn choose_eng_sub(lang: &Lang) -> Prio {
         return Prio::Avoid;
     }
 
-    let is_english = lang.ilang().any_equals(["eng", "en-US", "en", "english"]);
-    if !is_english {
+    let Some(lang_rank) = prefs.langs.iter().position(|l| lang.is_language(l)) else {
         return Prio::NotUse;
-    }
-
-    let is_specific = lang.ititle().any_equals(["for japanese audio", "dialogue"]);
-    if is_specific {
-        return Prio::Required;
-    }
+    };
 
-    let is_bad = lang
-        .ititle()
-        .any_contains(["signs", "forced", "dub", "dubtitle"])
-        || lang.title().any_contains(["SDH"]);
+    let is_bad = prefs.avoid_titles.iter().any(|k| lang.title_contains(k));
     if is_bad {
-        return Prio::Use;
-    }
-
-    Prio::Prefer
-}
-
-fn choose_jap_swe_eng_dub(lang: &Lang) -> Prio {
-    if lang.ilang().any_equals(["ja", "jpn", "japanese"]) {
-        return Prio::Required;
-    }
-
-    if lang.ilang().any_equals(["swe", "svenska", "sv"]) {
-        return Prio::Prefer;
-    }
-
-    if lang.ilang().any_equals(["en", "english", "eng"]) {
-        return Prio::Use;
+        return Prio::Use(Reverse(lang_rank));
     }
 
-    Prio::NotUse
+    let title_rank = prefs
+        .prefer_titles
+        .iter()
+        .position(|k| lang.title_contains(k))
+        .unwrap_or(prefs.prefer_titles.len());
+    Prio::Prefer(Reverse(lang_rank), Reverse(title_rank))
 }
 
 #[cfg(test)]
@@ -324,46 +358,64 @@ mod test {
         }
     }
 
+    fn prefs(langs: &[&str], prefer: &[&str], avoid: &[&str]) -> TrackPrefs {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect();
+        TrackPrefs {
+            langs: strings(langs),
+            prefer_titles: strings(prefer),
+            avoid_titles: strings(avoid),
+        }
+    }
+
+    fn eng_subs() -> TrackPrefs {
+        prefs(
+            &["en"],
+            &["for japanese audio", "dialogue"],
+            &["signs", "forced", "dub", "dubtitle", "SDH"],
+        )
+    }
+
+    fn jap_swe_eng_dubs() -> TrackPrefs {
+        prefs(&["ja", "sv", "en"], &[], &[])
+    }
+
     #[test]
     fn empty_lang_is_notuse() {
-        assert_eq!(Prio::NotUse, choose_jap_swe_eng_dub(&Lang::empty()));
-        assert_eq!(Prio::NotUse, choose_eng_sub(&Lang::empty()));
+        assert_eq!(Prio::NotUse, rank(&jap_swe_eng_dubs(), &Lang::empty()));
+        assert_eq!(Prio::NotUse, rank(&eng_subs(), &Lang::empty()));
     }
 
     #[test]
     fn empty_vec_does_nothing() {
-        let chosen: Option<(_, Lang)> = auto_choose(vec![], 0, HumanLang::English);
+        let chosen: Option<(_, Lang)> = auto_choose(vec![], 0, &eng_subs());
         assert_eq!(None, chosen);
     }
 
     #[test]
     fn dont_set_to_empty_if_all_are_notuse() {
         let italian = Lang::new_lang("ita");
-        assert_eq!(Prio::NotUse, choose_jap_swe_eng_dub(&italian));
+        assert_eq!(Prio::NotUse, rank(&jap_swe_eng_dubs(), &italian));
 
-        let chosen = auto_choose(vec![Lang::empty(), italian], 1, HumanLang::Japanese);
+        let chosen = auto_choose(vec![Lang::empty(), italian], 1, &jap_swe_eng_dubs());
         assert_eq!(None, chosen);
     }
 
     #[test]
     fn avoiding_selected() {
         let avoid = Lang::new_both("json", "live_chat");
-        assert_eq!(Prio::Avoid, choose_eng_sub(&avoid));
+        assert_eq!(Prio::Avoid, rank(&eng_subs(), &avoid));
 
-        let chosen =
-            auto_choose(vec![Lang::empty(), avoid.clone()], 1, HumanLang::English)
-                .map(|(i, _)| i);
+        let chosen = auto_choose(vec![Lang::empty(), avoid.clone()], 1, &eng_subs())
+            .map(|(i, _)| i);
         assert_eq!(Some(0), chosen);
 
-        let chosen =
-            auto_choose(vec![Lang::empty(), avoid.clone()], 0, HumanLang::English);
+        let chosen = auto_choose(vec![Lang::empty(), avoid.clone()], 0, &eng_subs());
         assert_eq!(None, chosen);
 
-        let chosen =
-            auto_choose(vec![avoid.clone(), avoid.clone()], 1, HumanLang::English);
+        let chosen = auto_choose(vec![avoid.clone(), avoid.clone()], 1, &eng_subs());
         assert_eq!(None, chosen);
 
-        let chosen = auto_choose(vec![avoid], 0, HumanLang::English);
+        let chosen = auto_choose(vec![avoid], 0, &eng_subs());
         assert_eq!(None, chosen);
     }
 
@@ -373,16 +425,16 @@ mod test {
     // https://github.com/rust-lang/rust/issues/50784
     fn different_kinds_of_arguments() {
         let v: Vec<Lang> = Vec::new();
-        auto_choose(v, 0, HumanLang::English);
+        auto_choose(v, 0, &eng_subs());
 
         let v: Vec<Lang> = Vec::new();
-        auto_choose(&v, 0, HumanLang::English);
+        auto_choose(&v, 0, &eng_subs());
 
         let v: Vec<&Lang> = Vec::new();
-        auto_choose(v, 0, HumanLang::English);
+        auto_choose(v, 0, &eng_subs());
 
         let v: Vec<&Lang> = Vec::new();
-        auto_choose(&v, 0, HumanLang::English);
+        auto_choose(&v, 0, &eng_subs());
 
         assert!(true);
     }
@@ -399,7 +451,7 @@ mod test {
             Lang::empty(),
             Lang::new_both("json", "live_chat"),
         ];
-        let prios: Vec<_> = preferred.iter().map(choose_eng_sub).collect();
+        let prios: Vec<_> = preferred.iter().map(|l| rank(&eng_subs(), l)).collect();
         assert!(prios.windows(2).all(|pair| pair[0] >= pair[1]));
     }
 
@@ -411,7 +463,10 @@ mod test {
             Lang::new_lang("eng"),
             Lang::empty(),
         ];
-        let prios: Vec<_> = preferred.iter().map(choose_jap_swe_eng_dub).collect();
+        let prios: Vec<_> = preferred
+            .iter()
+            .map(|l| rank(&jap_swe_eng_dubs(), l))
+            .collect();
         assert!(prios.windows(2).all(|pair| pair[0] > pair[1]));
     }
 
@@ -423,26 +478,23 @@ mod test {
                 Lang::new_both("Dialogue", "eng"),
             ],
             0,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(1), chosen.map(|(i, _)| i));
     }
 
     #[test]
     fn selects_selected_use() {
-        let chosen = auto_choose(
-            vec![Lang::empty(), Lang::new_lang("eng")],
-            1,
-            HumanLang::English,
-        );
+        let chosen =
+            auto_choose(vec![Lang::empty(), Lang::new_lang("eng")], 1, &eng_subs());
         assert_eq!(Some(1), chosen.map(|(i, _)| i));
     }
 
     #[test]
     fn sdh_is_case_sensitive() {
         assert_ne!(
-            choose_eng_sub(&Lang::new_both("SDH", "eng")),
-            choose_eng_sub(&Lang::new_both("sdh", "eng"))
+            rank(&eng_subs(), &Lang::new_both("SDH", "eng")),
+            rank(&eng_subs(), &Lang::new_both("sdh", "eng"))
         );
     }
 
@@ -451,7 +503,7 @@ mod test {
         let chosen = auto_choose(
             vec![Lang::new_lang("eng"), Lang::new_lang("eng")],
             0,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(0), chosen.map(|(i, _)| i));
     }
@@ -465,7 +517,7 @@ mod test {
                 Lang::new_both("For Japanese audio", "en"),
             ],
             1,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -479,7 +531,7 @@ mod test {
                 Lang::new_both("For Japanese audio", "en"),
             ],
             1,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -494,7 +546,7 @@ mod test {
                 Lang::new_both("Dub", "en"),
             ],
             1,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -509,7 +561,7 @@ mod test {
                 Lang::new_both("English (SDH)", "en"),
             ],
             1,
-            HumanLang::English,
+            &eng_subs(),
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -523,7 +575,7 @@ mod test {
                 Lang::new_both("AC3 5.1 ENG", "eng"),
             ],
             1,
-            HumanLang::Japanese,
+            &jap_swe_eng_dubs(),
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -533,7 +585,64 @@ mod test {
         let chosen = auto_choose(
             vec![Lang::empty(), Lang::new_lang("eng"), Lang::new_lang("jpn")],
             0,
-            HumanLang::Japanese,
+            &jap_swe_eng_dubs(),
+        );
+        assert_eq!(Some(2), chosen.map(|(i, _)| i));
+    }
+
+    #[test]
+    fn language_names() {
+        assert!(Lang::new_lang("eng").is_language("en"));
+        assert!(Lang::new_lang("en-US").is_language("english"));
+        assert!(Lang::new_lang("Svenska").is_language("swe"));
+        assert!(Lang::new_lang("tlh").is_language("TLH"));
+        assert!(!Lang::new_lang("eng").is_language("ja"));
+        assert!(!Lang::empty().is_language("en"));
+    }
+
+    #[test]
+    fn title_keywords() {
+        let lang = Lang::new_both("Full Subs (For Japanese Audio)", "en");
+        assert!(lang.title_contains("for japanese audio"));
+        assert!(lang.title_contains("subs"));
+        assert!(!lang.title_contains("SUBS"));
+        assert!(!lang.title_contains("for audio"));
+        assert!(!lang.title_contains(""));
+    }
+
+    #[test]
+    fn ranked_languages() {
+        let prefs = prefs(&["sv", "en"], &[], &["signs"]);
+        let chosen = auto_choose(
+            vec![
+                Lang::new_lang("eng"),
+                Lang::new_both("Signs", "swe"),
+                Lang::new_lang("swe"),
+            ],
+            0,
+            &prefs,
+        );
+        assert_eq!(Some(2), chosen.map(|(i, _)| i));
+
+        let chosen = auto_choose(
+            vec![Lang::new_both("Signs", "swe"), Lang::new_lang("eng")],
+            0,
+            &prefs,
+        );
+        assert_eq!(Some(1), chosen.map(|(i, _)| i));
+    }
+
+    #[test]
+    fn ranked_titles() {
+        let prefs = prefs(&["en"], &["full", "dialogue"], &[]);
+        let chosen = auto_choose(
+            vec![
+                Lang::new_lang("en"),
+                Lang::new_both("Dialogue", "en"),
+                Lang::new_both("Full", "en"),
+            ],
+            0,
+            &prefs,
         );
         assert_eq!(Some(2), chosen.map(|(i, _)| i));
     }
@@ -0,0 +1,154 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use protocol::to_client::front::filesearch;
+use tokio_util::sync::CancellationToken;
+
+use super::{headless::open, lang::Lang, TrackType};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What is in a media file
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Media {
+    pub duration: Option<Duration>,
+    /// The size of the video, not counting cover art
+    pub resolution: Option<(u32, u32)>,
+    pub tracks: Vec<MediaTrack>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MediaTrack {
+    pub ttype: TrackType,
+    pub codec: Option<String>,
+    pub lang: Option<String>,
+    pub title: Option<String>,
+}
+
+impl Media {
+    /// What the client is shown about the file
+    pub fn to_client(&self) -> filesearch::media::Media {
+        filesearch::media::Media {
+            duration: self.duration,
+            height: self.resolution.map(|(_, height)| height),
+            english_subs: self.has_subs("en"),
+        }
+    }
+
+    /// If there are subtitles in the language called `name`, under any of its names
+    pub fn has_subs(&self, name: &str) -> bool {
+        self.tracks
+            .iter()
+            .filter(|t| t.ttype == TrackType::Sub)
+            .any(|t| Lang::new(t.title.clone(), t.lang.clone()).is_language(name))
+    }
+}
+
+/// Open `video` without playing it, and look at what is in it
+pub fn probe(video: &Path, canceltoken: &CancellationToken) -> anyhow::Result<Media> {
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    let mpv = open(video, "null", deadline, canceltoken, |mpv| {
+        mpv.set_paused(true).synch()
+    })?;
+    let Some(mut mpv) = mpv else {
+        anyhow::bail!("the file could not be loaded");
+    };
+
+    let duration = mpv
+        .duration()
+        .synch()
+        .ok()
+        .and_then(|d| Duration::try_from_secs_f64(d).ok());
+    let tracks = mpv.track_list().synch().context("getting the tracks")?;
+    Ok(node_to_media(duration, &tracks))
+}
+
+fn node_to_media(duration: Option<Duration>, node: &libmpv::Node) -> Media {
+    let mut media = Media {
+        duration,
+        ..Media::default()
+    };
+
+    if let libmpv::Node::Array(nodes) = node {
+        for node in nodes {
+            if let libmpv::Node::Map(map) = node {
+                let Some(ttype) = map
+                    .get("type")
+                    .and_then(|ttype| ttype.try_to_string())
+                    .and_then(|ttype| match ttype {
+                        "audio" => Some(TrackType::Audio),
+                        "video" => Some(TrackType::Video),
+                        "sub" => Some(TrackType::Sub),
+                        _ => None,
+                    })
+                else {
+                    continue;
+                };
+
+                let string = |key| {
+                    map.get(key)
+                        .and_then(|s: &libmpv::Node| s.try_to_string())
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                };
+
+                let albumart = map
+                    .get("albumart")
+                    .and_then(|art| art.try_to_flag())
+                    .unwrap_or(false);
+                let size = |key| {
+                    map.get(key)
+                        .and_then(|s: &libmpv::Node| s.try_to_i64())
+                        .and_then(|s| u32::try_from(s).ok())
+                };
+                if ttype == TrackType::Video && !albumart && media.resolution.is_none() {
+                    if let (Some(w), Some(h)) = (size("demux-w"), size("demux-h")) {
+                        media.resolution = Some((w, h));
+                    }
+                }
+
+                media.tracks.push(MediaTrack {
+                    ttype,
+                    codec: string("codec"),
+                    lang: string("lang"),
+                    title: string("title"),
+                });
+            }
+        }
+    }
+
+    media
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn track(ttype: TrackType, lang: Option<&str>) -> MediaTrack {
+        MediaTrack {
+            ttype,
+            codec: None,
+            lang: lang.map(str::to_string),
+            title: None,
+        }
+    }
+
+    #[test]
+    fn sub_languages() {
+        let media = Media {
+            tracks: vec![
+                track(TrackType::Video, None),
+                track(TrackType::Audio, Some("eng")),
+                track(TrackType::Sub, Some("swe")),
+            ],
+            ..Media::default()
+        };
+        assert!(media.has_subs("sv"));
+        assert!(media.has_subs("swedish"));
+        assert!(!media.has_subs("en"));
+    }
+}
@@ -0,0 +1,116 @@
+use std::mem;
+
+use protocol::util::{Normal, Percent};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Seek {
+    Absolute(Percent<Normal>),
+    /// Seconds forward, or backward if negative
+    Relative(i64),
+}
+
+/// Makes sure only one seek at a time is sent to mpv. Seeks requested in the meantime
+/// are combined into as few as possible, which are sent when the one in flight is done.
+#[derive(Debug, Default)]
+pub struct Seeker {
+    in_flight: bool,
+    /// Where to seek to when the seek in flight is done
+    pending_absolute: Option<Percent<Normal>>,
+    /// Seconds to seek after that
+    pending_relative: i64,
+}
+
+impl Seeker {
+    /// Returns the seek to send right away, or `None` if it has to wait.
+    pub fn request(&mut self, seek: Seek) -> Option<Seek> {
+        if !self.in_flight {
+            self.in_flight = true;
+            return Some(seek);
+        }
+
+        match seek {
+            Seek::Absolute(target) => {
+                self.pending_absolute = Some(target);
+                self.pending_relative = 0;
+            }
+            Seek::Relative(secs) => self.pending_relative += secs,
+        }
+        None
+    }
+
+    /// The seek in flight is done. Returns the next one to send, if any.
+    pub fn done(&mut self) -> Option<Seek> {
+        let next = match self.pending_absolute.take() {
+            Some(target) => Some(Seek::Absolute(target)),
+            None if self.pending_relative != 0 => {
+                Some(Seek::Relative(mem::take(&mut self.pending_relative)))
+            }
+            None => None,
+        };
+        self.in_flight = next.is_some();
+        next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn abs(perc: f64) -> Seek {
+        Seek::Absolute(Percent::new(perc).unwrap())
+    }
+
+    #[test]
+    fn first_seek_is_immediate() {
+        let mut seeker = Seeker::default();
+        assert_eq!(Some(abs(10.0)), seeker.request(abs(10.0)));
+        assert_eq!(None, seeker.done());
+        assert_eq!(Some(Seek::Relative(5)), seeker.request(Seek::Relative(5)));
+    }
+
+    #[test]
+    fn keeps_latest_absolute() {
+        let mut seeker = Seeker::default();
+        seeker.request(abs(10.0));
+        for perc in [20.0, 30.0, 40.0] {
+            assert_eq!(None, seeker.request(abs(perc)));
+        }
+        assert_eq!(Some(abs(40.0)), seeker.done());
+        assert_eq!(None, seeker.done());
+    }
+
+    #[test]
+    fn sums_relative() {
+        let mut seeker = Seeker::default();
+        seeker.request(Seek::Relative(5));
+        for _ in 0..4 {
+            assert_eq!(None, seeker.request(Seek::Relative(5)));
+        }
+        assert_eq!(None, seeker.request(Seek::Relative(-30)));
+        assert_eq!(Some(Seek::Relative(-10)), seeker.done());
+        assert_eq!(None, seeker.done());
+    }
+
+    #[test]
+    fn relative_after_absolute() {
+        let mut seeker = Seeker::default();
+        seeker.request(Seek::Relative(5));
+        seeker.request(Seek::Relative(5));
+        seeker.request(abs(50.0));
+        seeker.request(Seek::Relative(30));
+        assert_eq!(Some(abs(50.0)), seeker.done());
+        assert_eq!(None, seeker.request(Seek::Relative(5)));
+        assert_eq!(Some(Seek::Relative(35)), seeker.done());
+        assert_eq!(None, seeker.done());
+    }
+
+    #[test]
+    fn cancelled_out() {
+        let mut seeker = Seeker::default();
+        seeker.request(abs(50.0));
+        seeker.request(Seek::Relative(5));
+        seeker.request(Seek::Relative(-5));
+        assert_eq!(None, seeker.done());
+        assert_eq!(Some(abs(1.0)), seeker.request(abs(1.0)));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use super::lang::language_group;
+
+/// Extensions of the subtitle files that mpv can load
+const SUB_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt", "sub"];
+
+/// Names of the folders next to the videos that have their subtitles
+const SUB_DIRS: &[&str] = &["subs", "subtitles"];
+
+/// A subtitle file that goes with a video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sidecar {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub lang: Option<String>,
+}
+
+/// Find the subtitle files that belong to `video`. They are the ones next to it whose
+/// names start with the video's, and the same in a `Subs/` folder. All subtitle files in
+/// `Subs/<video name>/` are also included.
+pub fn find(video: &Path) -> Vec<Sidecar> {
+    let (Some(dir), Some(stem)) = (video.parent(), video.file_stem()) else {
+        return Vec::new();
+    };
+
+    let mut sidecars = scan(dir, Some(stem));
+    for subdir in entries(dir).filter(|p| is_sub_dir(p)) {
+        sidecars.extend(scan(&subdir, Some(stem)));
+        sidecars.extend(scan(&subdir.join(stem), None));
+    }
+
+    sidecars.sort_by(|a, b| a.path.cmp(&b.path));
+    sidecars
+}
+
+fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+}
+
+fn is_sub_dir(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| SUB_DIRS.iter().any(|d| d.eq_ignore_ascii_case(name)))
+}
+
+/// The subtitle files in `dir`, only those that start with `stem` if given
+fn scan(dir: &Path, stem: Option<&OsStr>) -> Vec<Sidecar> {
+    entries(dir)
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let (title, lang) = parse(path.file_name()?, stem)?;
+            Some(Sidecar { path, title, lang })
+        })
+        .collect()
+}
+
+/// The title and language of the subtitle file called `name`, or `None` if it isn't a
+/// subtitle file or doesn't start with `stem`. Of the words in the name after `stem`, the
+/// first language becomes the language and the rest the title, as in
+/// "Video.en.forced.srt" or "2_English.srt".
+fn parse(name: &OsStr, stem: Option<&OsStr>) -> Option<(Option<String>, Option<String>)> {
+    // NOTE: compared as bytes, since the names don't have to be UTF-8
+    let name = match stem {
+        Some(stem) => {
+            let rest = name.as_bytes().strip_prefix(stem.as_bytes())?;
+            if rest.first().is_some_and(|b| !is_separator(char::from(*b))) {
+                return None;
+            }
+            String::from_utf8_lossy(rest)
+        }
+        None => name.to_string_lossy(),
+    };
+
+    let (rest, ext) = name.rsplit_once('.')?;
+    if !SUB_EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
+        return None;
+    }
+
+    let mut lang = None;
+    let mut title = Vec::new();
+    for word in rest.split(is_separator).filter(|w| !w.is_empty()) {
+        if lang.is_none() && language_group(word).is_some() {
+            lang = Some(word.to_string());
+        } else if !word.chars().all(|c| c.is_ascii_digit()) {
+            title.push(word);
+        }
+    }
+
+    let title = (!title.is_empty()).then(|| title.join(" "));
+    Some((title, lang))
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '.' | '_' | '-' | ' ' | '[' | ']' | '(' | ')')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    fn parse(name: &str, stem: Option<&str>) -> Option<(Option<String>, Option<String>)> {
+        super::parse(OsStr::new(name), stem.map(OsStr::new))
+    }
+
+    #[test]
+    fn next_to_video() {
+        let stem = Some("Show S01E01");
+        assert_eq!(Some((None, None)), parse("Show S01E01.srt", stem));
+        assert_eq!(Some((None, some("en"))), parse("Show S01E01.en.srt", stem));
+        assert_eq!(
+            Some((some("forced"), some("eng"))),
+            parse("Show S01E01.eng.forced.ass", stem)
+        );
+        assert_eq!(
+            Some((some("SDH"), some("English"))),
+            parse("Show S01E01 [English] (SDH).VTT", stem)
+        );
+    }
+
+    #[test]
+    fn not_subtitles() {
+        let stem = Some("Show S01E01");
+        assert_eq!(None, parse("Show S01E01.mkv", stem));
+        assert_eq!(None, parse("Show S01E01", stem));
+        assert_eq!(None, parse("Show S01E010.en.srt", stem));
+        assert_eq!(None, parse("Other.en.srt", stem));
+    }
+
+    #[test]
+    fn not_utf8() {
+        let stem = OsStr::from_bytes(b"Show \xff");
+        assert_eq!(
+            Some((None, some("en"))),
+            super::parse(OsStr::from_bytes(b"Show \xff.en.srt"), Some(stem))
+        );
+        assert_eq!(
+            None,
+            super::parse(OsStr::from_bytes(b"Show \xfe.en.srt"), Some(stem))
+        );
+    }
+
+    #[test]
+    fn in_subs_folder() {
+        assert_eq!(Some((None, some("English"))), parse("2_English.srt", None));
+        assert_eq!(
+            Some((some("Signs Songs"), some("eng"))),
+            parse("3_eng_Signs_Songs.ass", None)
+        );
+        assert_eq!(
+            Some((some("Commentary"), None)),
+            parse("Commentary.srt", None)
+        );
+    }
+}
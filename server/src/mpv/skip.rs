@@ -0,0 +1,241 @@
+use std::{collections::HashMap, path::Path};
+
+use protocol::to_client::front::mpv::playstate::Segment;
+
+use crate::config::SkipConf;
+
+use super::{lang::contains_keyword, State};
+
+/// Don't offer to skip what ends sooner than this many seconds from now
+const MIN_LEFT: f64 = 1.0;
+
+/// The start and end in seconds of the intro and outro of a video
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Markers {
+    intro: Option<(f64, f64)>,
+    outro: Option<(f64, f64)>,
+}
+
+/// The contents of the marker file. The markers at the top are for all the videos in its
+/// directory, and those in `files` for the video with that file name only.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MarkerFile {
+    intro: Option<(f64, f64)>,
+    outro: Option<(f64, f64)>,
+    #[serde(default)]
+    files: HashMap<String, Markers>,
+}
+
+impl MarkerFile {
+    fn markers(mut self, name: &str) -> Markers {
+        let file = self.files.remove(name).unwrap_or_default();
+        Markers {
+            intro: file.intro.or(self.intro),
+            outro: file.outro.or(self.outro),
+        }
+    }
+}
+
+/// Read the markers of `video` from the marker file next to it. There are no markers if
+/// it doesn't exist or can't be read.
+pub async fn read_markers(video: &Path, conf: &SkipConf) -> Markers {
+    let (Some(dir), Some(name)) = (video.parent(), video.file_name()) else {
+        return Markers::default();
+    };
+    let file = dir.join(&conf.marker_file);
+    let conts = match tokio::fs::read_to_string(&file).await {
+        Ok(conts) => conts,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Markers::default(),
+        Err(e) => {
+            log::warn!("Failed to read the marker file {file:?}: {e}");
+            return Markers::default();
+        }
+    };
+
+    toml::from_str::<MarkerFile>(&conts)
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to parse the marker file {file:?}: {e}");
+            MarkerFile::default()
+        })
+        .markers(&name.to_string_lossy())
+}
+
+/// Finds the intros and outros, and skips them if it should
+#[derive(Debug)]
+pub struct Skipper {
+    intro_chapters: Vec<String>,
+    outro_chapters: Vec<String>,
+    markers: Markers,
+    auto: bool,
+    /// Where the parts that have been skipped automatically end, so that they aren't
+    /// skipped again if the user goes back to them
+    auto_skipped: Vec<f64>,
+}
+
+impl Skipper {
+    pub fn new(conf: &SkipConf, markers: Markers, auto: bool) -> Self {
+        Self {
+            intro_chapters: conf.intro_chapters.clone(),
+            outro_chapters: conf.outro_chapters.clone(),
+            markers,
+            auto,
+            auto_skipped: Vec::new(),
+        }
+    }
+
+    /// The part being played that can be skipped, and the second where it ends
+    pub(super) fn skippable(&self, state: &State) -> Option<(Segment, f64)> {
+        let time = state.playback_time;
+        let from_markers = [
+            (Segment::Intro, self.markers.intro),
+            (Segment::Outro, self.markers.outro),
+        ]
+        .into_iter()
+        .find_map(|(segment, range)| match range {
+            Some((start, end)) if start <= time && time < end => Some((segment, end)),
+            _ => None,
+        });
+
+        from_markers
+            .or_else(|| self.chapter_segment(state))
+            .filter(|(_, end)| time + MIN_LEFT < *end)
+    }
+
+    fn chapter_segment(&self, state: &State) -> Option<(Segment, f64)> {
+        let i = usize::try_from(state.chapter).ok()?;
+        let title = state.chapter_list.get(i)?.title.as_deref()?;
+        let matches =
+            |keywords: &[String]| keywords.iter().any(|k| contains_keyword(title, k));
+
+        let segment = if matches(&self.intro_chapters) {
+            Segment::Intro
+        } else if matches(&self.outro_chapters) {
+            Segment::Outro
+        } else {
+            return None;
+        };
+
+        let end = state
+            .chapter_list
+            .get(i + 1)
+            .map_or(Some(state.duration), |next| next.time)?;
+        Some((segment, end))
+    }
+
+    /// Where to seek to if `skippable` should be skipped without asking
+    pub fn auto_skip(&mut self, skippable: Option<&(Segment, f64)>) -> Option<f64> {
+        let &(_, end) = skippable.filter(|_| self.auto)?;
+        if self.auto_skipped.contains(&end) {
+            return None;
+        }
+        self.auto_skipped.push(end);
+        Some(end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Chapter;
+    use super::*;
+
+    fn conf() -> SkipConf {
+        SkipConf {
+            intro_chapters: vec!["opening".to_string(), "OP".to_string()],
+            outro_chapters: vec!["ending".to_string(), "ED".to_string()],
+            marker_file: ".skip.toml".to_string(),
+            auto_roots: Vec::new(),
+        }
+    }
+
+    fn state(time: f64, chapter: i64, titles: &[&str]) -> State {
+        State {
+            playback_time: time,
+            duration: 1400.0,
+            chapter,
+            chapter_list: titles
+                .iter()
+                .zip([0.0, 90.0, 1300.0])
+                .map(|(title, time)| Chapter {
+                    title: Some(title.to_string()),
+                    time: Some(time),
+                })
+                .collect(),
+            ..State::default()
+        }
+    }
+
+    #[test]
+    fn chapter_titles() {
+        let skipper = Skipper::new(&conf(), Markers::default(), false);
+        let titles = ["OP", "Part A", "Ending"];
+        assert_eq!(
+            Some((Segment::Intro, 90.0)),
+            skipper.skippable(&state(10.0, 0, &titles))
+        );
+        assert_eq!(None, skipper.skippable(&state(100.0, 1, &titles)));
+        assert_eq!(
+            Some((Segment::Outro, 1400.0)),
+            skipper.skippable(&state(1310.0, 2, &titles))
+        );
+
+        let titles = ["Stop", "Opening Song", "Part B"];
+        assert_eq!(None, skipper.skippable(&state(10.0, 0, &titles)));
+        assert_eq!(
+            Some((Segment::Intro, 1300.0)),
+            skipper.skippable(&state(100.0, 1, &titles))
+        );
+    }
+
+    #[test]
+    fn next_chapter_without_time() {
+        let skipper = Skipper::new(&conf(), Markers::default(), false);
+        let mut state = state(10.0, 0, &["OP", "Part A"]);
+        state.chapter_list[1].time = None;
+        assert_eq!(None, skipper.skippable(&state));
+    }
+
+    #[test]
+    fn marker_file() {
+        let markers: Markers = toml::from_str("intro = [30.0, 120.0]").unwrap();
+        let skipper = Skipper::new(&conf(), markers, false);
+        assert_eq!(None, skipper.skippable(&state(10.0, -1, &[])));
+        assert_eq!(
+            Some((Segment::Intro, 120.0)),
+            skipper.skippable(&state(30.0, -1, &[]))
+        );
+        assert_eq!(None, skipper.skippable(&state(119.5, -1, &[])));
+    }
+
+    #[test]
+    fn markers_by_file() {
+        let file: MarkerFile = toml::from_str(
+            r#"
+            intro = [30.0, 120.0]
+            outro = [1300.0, 1390.0]
+            [files."02.mkv"]
+            intro = [0.0, 90.0]
+            "#,
+        )
+        .unwrap();
+        let markers = file.markers("02.mkv");
+        assert_eq!(Some((0.0, 90.0)), markers.intro);
+        assert_eq!(Some((1300.0, 1390.0)), markers.outro);
+
+        let file: MarkerFile = toml::from_str("intro = [30.0, 120.0]").unwrap();
+        assert_eq!(Some((30.0, 120.0)), file.markers("01.mkv").intro);
+    }
+
+    #[test]
+    fn auto_skips_once() {
+        let mut skipper = Skipper::new(&conf(), Markers::default(), true);
+        let intro = Some(&(Segment::Intro, 90.0));
+        assert_eq!(Some(90.0), skipper.auto_skip(intro));
+        assert_eq!(None, skipper.auto_skip(intro));
+        assert_eq!(None, skipper.auto_skip(None));
+
+        let mut skipper = Skipper::new(&conf(), Markers::default(), false);
+        assert_eq!(None, skipper.auto_skip(intro));
+    }
+}
@@ -0,0 +1,593 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use libmpv::{EndReason, Event};
+use protocol::{
+    to_client::preview::{sprites::Sprites, thumbnail::Thumbnail},
+    util::FileId,
+    ToClientable,
+};
+use tokio::{
+    select,
+    sync::mpsc,
+    task::{spawn_blocking, JoinError, JoinHandle},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    caster::{self, ClientId, ToConn},
+    config,
+};
+
+use super::headless::{has_video, open, wait_for};
+
+/// The size of the thumbnails shown next to the files
+const THUMBNAIL_WIDTH: u32 = 320;
+/// How far into the file the thumbnail is taken
+const THUMBNAIL_POSITION: &str = "20%";
+const THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The size of each tile in the sprite sheet
+const SPRITE_WIDTH: u32 = 160;
+const SPRITE_HEIGHT: u32 = 90;
+const SPRITE_COLUMNS: u32 = 10;
+/// The time between the tiles, unless the file is too long to fit them all
+const SPRITE_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_SPRITES: u32 = 1000;
+const SPRITES_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many thumbnails are remembered, the ones asked for first are forgotten since they
+/// probably aren't shown anymore
+const MAX_WANTED: usize = 200;
+
+pub type Sender = mpsc::Sender<Request>;
+pub type Receiver = mpsc::Receiver<Request>;
+
+#[derive(Debug)]
+pub enum Request {
+    /// Send thumbnails of `videos` to `client`, the first one first
+    Thumbnails {
+        client: ClientId,
+        videos: Vec<(FileId, PathBuf)>,
+    },
+    /// Start making the sprites of `video`, which is now playing
+    Play { video: PathBuf },
+    /// Nothing is playing anymore, so the sprites aren't needed
+    Stop,
+    /// Send the sprites of what is playing to `client`, when they are done
+    Sprites { client: ClientId },
+}
+
+pub fn thumbnail_dir() -> PathBuf {
+    config::cache_dir().join("thumbnails")
+}
+
+type Job<T> = JoinHandle<anyhow::Result<Option<T>>>;
+
+/// A thumbnail that has been asked for, and who asked
+#[derive(Debug)]
+struct Wanted {
+    id: FileId,
+    video: PathBuf,
+    clients: Vec<ClientId>,
+}
+
+/// The thumbnails to make, the last one asked for is made first since that is what is
+/// shown right now
+#[derive(Debug, Default)]
+struct WantedQueue {
+    queue: VecDeque<Wanted>,
+}
+
+impl WantedQueue {
+    /// Ask for `id` for `client`, or make it the next one if it was asked for already
+    fn push(&mut self, client: ClientId, id: FileId, video: PathBuf) {
+        let mut clients = match self.queue.iter().position(|w| w.id == id) {
+            Some(i) => self.queue.remove(i).expect("in range").clients,
+            None => Vec::new(),
+        };
+        if !clients.contains(&client) {
+            clients.push(client);
+        }
+        self.queue.push_back(Wanted { id, video, clients });
+
+        if self.queue.len() > MAX_WANTED {
+            if let Some(old) = self.queue.pop_front() {
+                log::debug!("Too many thumbnails asked for, forgetting {:?}", old.id);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Wanted> {
+        self.queue.pop_back()
+    }
+}
+
+/// The sprites of what is playing
+enum Playing {
+    Making {
+        video: PathBuf,
+        job: Job<Sprites>,
+        cancel: CancellationToken,
+        /// The clients that have asked for the sprites
+        waiting: Vec<ClientId>,
+    },
+    Done(Option<Sprites>),
+}
+
+impl Playing {
+    fn stop(self) {
+        if let Playing::Making { video, cancel, .. } = self {
+            log::debug!("Cancelling the sprites of {}", video.display());
+            cancel.cancel();
+        }
+    }
+}
+
+/// Take pictures of the videos asked for in `requests`. Thumbnails are made one at a time
+/// while the sprites of what is playing are made on the side. They are saved in
+/// [`thumbnail_dir`] so that each file only has to be done once.
+pub async fn thumbnailer_actor(
+    mut requests: Receiver,
+    to_conn: caster::Sender,
+    canceltoken: CancellationToken,
+) {
+    let mut wanted = WantedQueue::default();
+    let mut making: Option<(Wanted, Job<Vec<u8>>)> = None;
+    let mut playing: Option<Playing> = None;
+    // NOTE: files that failed are not tried again until the next restart
+    let mut failed = HashSet::new();
+
+    'actor: loop {
+        if making.is_none() {
+            if let Some(next) = wanted.pop() {
+                let job = spawn_cached(&next.video, &canceltoken, thumbnail);
+                making = Some((next, job));
+            }
+        }
+
+        let making_sprites = matches!(playing, Some(Playing::Making { .. }));
+        let sprites_job = match &mut playing {
+            Some(Playing::Making { job, .. }) => Some(job),
+            _ => None,
+        };
+        let msgs = select! {
+            _ = canceltoken.cancelled() => break,
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    break;
+                };
+                handle_request(
+                    request,
+                    &mut wanted,
+                    &mut making,
+                    &mut playing,
+                    &failed,
+                    &canceltoken,
+                )
+            }
+            res = async { (&mut making.as_mut().expect("checked").1).await },
+                if making.is_some() => {
+                let (done, _) = making.take().expect("checked");
+                let jpeg = finished(&done.video, res, &mut failed);
+                let msg = Thumbnail { id: done.id, jpeg }.to_client();
+                done.clients.into_iter().map(|c| ToConn::One(c, msg.clone())).collect()
+            }
+            res = async { sprites_job.expect("checked").await },
+                if making_sprites => {
+                let Some(Playing::Making { video, waiting, .. }) = playing.take() else {
+                    unreachable!("checked");
+                };
+                let sprites = finished(&video, res, &mut failed);
+                let msgs = match &sprites {
+                    Some(sprites) => waiting
+                        .into_iter()
+                        .map(|c| ToConn::One(c, sprites.clone().to_client()))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                playing = Some(Playing::Done(sprites));
+                msgs
+            }
+        };
+
+        for msg in msgs {
+            if to_conn.send(msg).await.is_err() {
+                log::warn!("Seems like connections is down");
+                break 'actor;
+            }
+        }
+    }
+
+    if let Some(playing) = playing {
+        playing.stop();
+    }
+    log::info!("Thumbnailer actor exited");
+}
+
+/// Keep track of `request`, and give what can be sent right away
+fn handle_request(
+    request: Request,
+    wanted: &mut WantedQueue,
+    making: &mut Option<(Wanted, Job<Vec<u8>>)>,
+    playing: &mut Option<Playing>,
+    failed: &HashSet<PathBuf>,
+    canceltoken: &CancellationToken,
+) -> Vec<ToConn> {
+    match request {
+        Request::Thumbnails { client, videos } => {
+            let mut msgs = Vec::new();
+            for (id, video) in videos.into_iter().rev() {
+                match making {
+                    _ if failed.contains(&video) => msgs.push(ToConn::One(
+                        client,
+                        Thumbnail { id, jpeg: None }.to_client(),
+                    )),
+                    Some((current, _)) if current.id == id => {
+                        if !current.clients.contains(&client) {
+                            current.clients.push(client);
+                        }
+                    }
+                    _ => wanted.push(client, id, video),
+                }
+            }
+            msgs
+        }
+        Request::Play { video } => {
+            if let Some(old) = playing.take() {
+                old.stop();
+            }
+            *playing = if failed.contains(&video) {
+                Some(Playing::Done(None))
+            } else {
+                let cancel = canceltoken.child_token();
+                Some(Playing::Making {
+                    job: spawn_cached(&video, &cancel, sprites),
+                    video,
+                    cancel,
+                    waiting: Vec::new(),
+                })
+            };
+            Vec::new()
+        }
+        Request::Stop => {
+            if let Some(old) = playing.take() {
+                old.stop();
+            }
+            Vec::new()
+        }
+        Request::Sprites { client } => match playing {
+            Some(Playing::Making { waiting, .. }) => {
+                if !waiting.contains(&client) {
+                    waiting.push(client);
+                }
+                Vec::new()
+            }
+            Some(Playing::Done(Some(sprites))) => {
+                vec![ToConn::One(client, sprites.clone().to_client())]
+            }
+            Some(Playing::Done(None)) | None => Vec::new(),
+        },
+    }
+}
+
+/// Make what `generate` makes of `video` on a blocking thread
+fn spawn_cached<T>(
+    video: &Path,
+    canceltoken: &CancellationToken,
+    generate: fn(&Path, &Path, &str, &CancellationToken) -> anyhow::Result<Option<T>>,
+) -> Job<T>
+where
+    T: Send + 'static,
+{
+    let video = video.to_owned();
+    let canceltoken = canceltoken.clone();
+    spawn_blocking(move || cached(&thumbnail_dir(), &video, &canceltoken, generate))
+}
+
+/// What a job gave, remembering `video` as failed if it did
+fn finished<T>(
+    video: &Path,
+    res: Result<anyhow::Result<Option<T>>, JoinError>,
+    failed: &mut HashSet<PathBuf>,
+) -> Option<T> {
+    let err = match res {
+        Ok(Ok(t)) => return t,
+        Ok(Err(e)) => e,
+        Err(e) => anyhow::Error::from(e).context("the job panicked"),
+    };
+    log::warn!("Failed to make pictures of {}: {err:?}", video.display());
+    failed.insert(video.to_owned());
+    None
+}
+
+/// Read what `generate` makes of `video` from `dir`, or make it and save it there
+fn cached<T>(
+    dir: &Path,
+    video: &Path,
+    canceltoken: &CancellationToken,
+    generate: fn(&Path, &Path, &str, &CancellationToken) -> anyhow::Result<Option<T>>,
+) -> anyhow::Result<Option<T>> {
+    let key = key(video).context("reading the file")?;
+    fs::create_dir_all(dir).context("creating the thumbnail dir")?;
+    generate(dir, video, &key, canceltoken)
+}
+
+/// A name for `video` that changes if the file does
+fn key(video: &Path) -> std::io::Result<String> {
+    let meta = fs::metadata(video)?;
+    let mut hash = fnv1a(FNV_OFFSET, video.as_os_str().as_bytes());
+    hash = fnv1a(hash, &meta.len().to_le_bytes());
+    hash = fnv1a(hash, &meta.mtime().to_le_bytes());
+    hash = fnv1a(hash, &meta.mtime_nsec().to_le_bytes());
+    Ok(format!("{hash:016x}"))
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn thumbnail(
+    dir: &Path,
+    video: &Path,
+    key: &str,
+    canceltoken: &CancellationToken,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let file = dir.join(format!("{key}.jpg"));
+    if file.exists() {
+        return fs::read(&file).context("reading the thumbnail").map(Some);
+    }
+
+    let filter = format!("lavfi=[scale={THUMBNAIL_WIDTH}:-2]");
+    let taken = take_pictures(video, &file, THUMBNAIL_TIMEOUT, canceltoken, |mpv| {
+        mpv.set_start(THUMBNAIL_POSITION).synch()?;
+        mpv.set_video_filter(filter.as_str()).synch()
+    })?;
+
+    match taken {
+        Some(()) => fs::read(&file).context("reading the thumbnail").map(Some),
+        None => Ok(None),
+    }
+}
+
+fn sprites(
+    dir: &Path,
+    video: &Path,
+    key: &str,
+    canceltoken: &CancellationToken,
+) -> anyhow::Result<Option<Sprites>> {
+    let file = dir.join(format!("{key}.sprites"));
+    if file.exists() {
+        let file = fs::File::open(&file).context("opening the sprites")?;
+        return bincode::deserialize_from(file)
+            .context("reading the sprites")
+            .map(Some);
+    }
+
+    let Some(layout) = probe_duration(video, canceltoken)?.and_then(Layout::new) else {
+        return Ok(None);
+    };
+
+    let image = dir.join(format!("{key}.sprites.jpg"));
+    let filter = layout.filter();
+    let taken = take_pictures(video, &image, SPRITES_TIMEOUT, canceltoken, |mpv| {
+        // NOTE: the frames in between are not needed, and decoding them takes a long time
+        mpv.set_vd_lavc_skip_frame("nonkey").synch()?;
+        mpv.set_video_filter(filter.as_str()).synch()
+    })?;
+    if taken.is_none() {
+        return Ok(None);
+    }
+    let jpeg = fs::read(&image).context("reading the sprites")?;
+    fs::remove_file(&image).ok();
+
+    let sprites = Sprites {
+        interval: layout.interval,
+        columns: layout.columns,
+        rows: layout.rows,
+        count: layout.count,
+        jpeg,
+    };
+    let mut out = fs::File::create(&file).context("creating the sprites")?;
+    bincode::serialize_into(&mut out, &sprites).context("writing the sprites")?;
+    Ok(Some(sprites))
+}
+
+/// How the frames are laid out in the sprite sheet
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+    interval: Duration,
+    columns: u32,
+    rows: u32,
+    count: u32,
+}
+
+impl Layout {
+    fn new(duration: Duration) -> Option<Self> {
+        if duration.is_zero() {
+            return None;
+        }
+        let interval = SPRITE_INTERVAL.max(duration / MAX_SPRITES);
+        let count = (duration.as_millis().div_ceil(interval.as_millis()) as u32).max(1);
+        Some(Self {
+            interval,
+            columns: SPRITE_COLUMNS.min(count),
+            rows: count.div_ceil(SPRITE_COLUMNS),
+            count,
+        })
+    }
+
+    fn filter(&self) -> String {
+        format!(
+            "lavfi=[fps=1000/{},scale={SPRITE_WIDTH}:{SPRITE_HEIGHT}\
+             :force_original_aspect_ratio=decrease,pad={SPRITE_WIDTH}:{SPRITE_HEIGHT}\
+             :-1:-1,tile={}x{}]",
+            self.interval.as_millis(),
+            self.columns,
+            self.rows
+        )
+    }
+}
+
+/// Play `video` without a window and save the first frame that is shown as `out`.
+/// `setup` is called before the file is loaded, and can set what should be shown. Gives
+/// `None` if the file doesn't have any pictures.
+fn take_pictures<F>(
+    video: &Path,
+    out: &Path,
+    timeout: Duration,
+    canceltoken: &CancellationToken,
+    setup: F,
+) -> anyhow::Result<Option<()>>
+where
+    F: FnOnce(&mut libmpv::Handle<libmpv::Sync>) -> libmpv::Result<()>,
+{
+    let outdir = out.with_extension("tmp");
+    fs::create_dir_all(&outdir).context("creating the output dir")?;
+    let res = write_frame(video, &outdir, timeout, canceltoken, setup);
+    let first = outdir.join("00000001.jpg");
+    let res = match res {
+        Ok(true) if first.exists() => fs::rename(&first, out)
+            .context("moving the picture")
+            .map(Some),
+        Ok(_) => Ok(None),
+        Err(e) => Err(e),
+    };
+    fs::remove_dir_all(&outdir).ok();
+    res
+}
+
+/// Gives `false` if there is no video
+fn write_frame<F>(
+    video: &Path,
+    outdir: &Path,
+    timeout: Duration,
+    canceltoken: &CancellationToken,
+    setup: F,
+) -> anyhow::Result<bool>
+where
+    F: FnOnce(&mut libmpv::Handle<libmpv::Sync>) -> libmpv::Result<()>,
+{
+    let deadline = Instant::now() + timeout;
+    // NOTE: the image output only writes the frames to disk, so no GPU is needed
+    let mpv = open(video, "image", deadline, canceltoken, |mpv| {
+        mpv.set_vo_image_format("jpg").synch()?;
+        mpv.set_vo_image_outdir(outdir).synch()?;
+        mpv.set_frames(1).synch()?;
+        setup(mpv)
+    })?;
+    let Some(mut mpv) = mpv else {
+        return Ok(false);
+    };
+    if !has_video(&mut mpv)? {
+        return Ok(false);
+    }
+
+    wait_for(&mut mpv, deadline, canceltoken, |event| match event {
+        Event::EndFile {
+            reason: EndReason::Error,
+            error,
+        } => Some(Err(anyhow::anyhow!("mpv failed to play: {error:?}"))),
+        Event::EndFile { .. } | Event::Shutdown => Some(Ok(true)),
+        _ => None,
+    })
+}
+
+/// How long `video` is, if it has any video
+fn probe_duration(
+    video: &Path,
+    canceltoken: &CancellationToken,
+) -> anyhow::Result<Option<Duration>> {
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    let mpv = open(video, "null", deadline, canceltoken, |mpv| {
+        mpv.set_paused(true).synch()
+    })?;
+    let Some(mut mpv) = mpv else {
+        return Ok(None);
+    };
+    if !has_video(&mut mpv)? {
+        return Ok(None);
+    }
+
+    let duration = mpv.duration().synch().context("getting the duration")?;
+    Ok(Duration::try_from_secs_f64(duration).ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn short_layout() {
+        assert_eq!(
+            Some(Layout {
+                interval: SPRITE_INTERVAL,
+                columns: 3,
+                rows: 1,
+                count: 3
+            }),
+            Layout::new(Duration::from_secs(25))
+        );
+        assert_eq!(None, Layout::new(Duration::ZERO));
+    }
+
+    #[test]
+    fn long_layout() {
+        let l = Layout::new(Duration::from_secs(95)).unwrap();
+        assert_eq!((10, 1, 10), (l.columns, l.rows, l.count));
+
+        let l = Layout::new(Duration::from_secs(24 * 60 * 60)).unwrap();
+        assert!(l.count <= MAX_SPRITES);
+        assert!(l.interval > SPRITE_INTERVAL);
+        assert_eq!(l.count, l.columns * l.rows);
+    }
+
+    #[test]
+    fn wanted_last_first() {
+        let (a, b) = (ClientId::unique(), ClientId::unique());
+        let file = |i| (FileId::new(i, 0), PathBuf::from(format!("{i}.mkv")));
+        let mut wanted = WantedQueue::default();
+        for i in 0..3 {
+            let (id, video) = file(i);
+            wanted.push(a, id, video);
+        }
+        let (id, video) = file(0);
+        wanted.push(b, id, video);
+
+        let first = wanted.pop().unwrap();
+        assert_eq!((FileId::new(0, 0), vec![a, b]), (first.id, first.clients));
+        assert_eq!(FileId::new(2, 0), wanted.pop().unwrap().id);
+        assert_eq!(FileId::new(1, 0), wanted.pop().unwrap().id);
+        assert!(wanted.pop().is_none());
+    }
+
+    #[test]
+    fn wanted_forgets_oldest() {
+        let client = ClientId::unique();
+        let mut wanted = WantedQueue::default();
+        for i in 0..=MAX_WANTED {
+            wanted.push(client, FileId::new(i, 0), PathBuf::from("a.mkv"));
+        }
+        assert_eq!(MAX_WANTED, wanted.queue.len());
+        assert_eq!(FileId::new(1, 0), wanted.queue[0].id);
+    }
+
+    #[test]
+    fn fnv_known_value() {
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a(FNV_OFFSET, b"a"));
+    }
+}
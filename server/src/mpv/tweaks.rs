@@ -0,0 +1,88 @@
+/// The aspect ratios to cycle through, after the video's own
+const ASPECTS: &[(&str, f64)] = &[
+    ("4:3", 4.0 / 3.0),
+    ("16:9", 16.0 / 9.0),
+    ("1.85:1", 1.85),
+    ("2.35:1", 2.35),
+];
+
+fn preset(aspect: f64) -> Option<usize> {
+    ASPECTS
+        .iter()
+        .position(|(_, ratio)| (ratio - aspect).abs() < 0.01)
+}
+
+/// The name of the `video-aspect-override`, `None` if the video's own is used
+pub fn aspect_name(aspect: f64) -> Option<String> {
+    if aspect < 0.0 {
+        None
+    } else if aspect == 0.0 {
+        Some("square pixels".to_string())
+    } else {
+        Some(match preset(aspect) {
+            Some(i) => ASPECTS[i].0.to_string(),
+            None => format!("{aspect:.2}:1"),
+        })
+    }
+}
+
+/// The `video-aspect-override` after `aspect`, -1 being the video's own
+pub fn next_aspect(aspect: f64) -> f64 {
+    match preset(aspect) {
+        Some(i) if i + 1 < ASPECTS.len() => ASPECTS[i + 1].1,
+        Some(_) => -1.0,
+        None if aspect < 0.0 => ASPECTS[0].1,
+        None => -1.0,
+    }
+}
+
+/// The time of `ab-loop-a` or `ab-loop-b`, which is "no" if it isn't set
+pub fn loop_point(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|t: &f64| t.is_finite())
+}
+
+/// If `loop-file` is set to loop at least once
+pub fn is_looping(value: &str) -> bool {
+    !matches!(value, "no" | "0")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cycle_aspects() {
+        let mut aspect = -1.0;
+        let mut names = Vec::new();
+        for _ in 0..=ASPECTS.len() {
+            aspect = next_aspect(aspect);
+            names.push(aspect_name(aspect));
+        }
+        assert_eq!(
+            vec![
+                Some("4:3".to_string()),
+                Some("16:9".to_string()),
+                Some("1.85:1".to_string()),
+                Some("2.35:1".to_string()),
+                None
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn custom_aspect() {
+        assert_eq!(Some("2.00:1".to_string()), aspect_name(2.0));
+        assert_eq!(-1.0, next_aspect(2.0));
+        assert_eq!(Some("16:9".to_string()), aspect_name(1.777778));
+    }
+
+    #[test]
+    fn loop_values() {
+        assert_eq!(None, loop_point("no"));
+        assert_eq!(Some(12.5), loop_point("12.500000"));
+        assert!(is_looping("inf"));
+        assert!(is_looping("3"));
+        assert!(!is_looping("no"));
+    }
+}
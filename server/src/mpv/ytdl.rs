@@ -0,0 +1,91 @@
+use protocol::to_server::mpvstart::url::Quality;
+
+use crate::config::YtdlPolicy;
+
+/// The policy to use when the client asked for `quality`
+pub fn with_quality(policy: &YtdlPolicy, quality: &Quality) -> YtdlPolicy {
+    match quality {
+        Quality::Default => policy.clone(),
+        Quality::MaxHeight(height) => YtdlPolicy {
+            max_height: Some(*height),
+            audio_only: false,
+            ..policy.clone()
+        },
+        Quality::AudioOnly => YtdlPolicy {
+            audio_only: true,
+            ..policy.clone()
+        },
+    }
+}
+
+/// The value of mpv's `ytdl-format` that follows `policy`. Falls back to any resolution
+/// if there is nothing below the maximum.
+pub fn format(policy: &YtdlPolicy) -> String {
+    if policy.audio_only {
+        return "bestaudio/best".to_string();
+    }
+
+    let height = policy
+        .max_height
+        .map(|h| format!("[height<={h}]"))
+        .unwrap_or_default();
+
+    let mut formats: Vec<_> = policy
+        .codecs
+        .iter()
+        .map(|codec| format!("bestvideo{height}[vcodec^={codec}]+bestaudio"))
+        .collect();
+    formats.push(format!("bestvideo{height}+bestaudio"));
+    formats.push(format!("best{height}"));
+    if !height.is_empty() {
+        formats.push("bestvideo+bestaudio".to_string());
+        formats.push("best".to_string());
+    }
+
+    formats.join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy(max_height: Option<u32>, codecs: &[&str]) -> YtdlPolicy {
+        YtdlPolicy {
+            max_height,
+            codecs: codecs.iter().map(|c| c.to_string()).collect(),
+            audio_only: false,
+        }
+    }
+
+    #[test]
+    fn max_height() {
+        assert_eq!(
+            "bestvideo[height<=1080]+bestaudio/best[height<=1080]/bestvideo+bestaudio/best",
+            format(&policy(Some(1080), &[]))
+        );
+        assert_eq!("bestvideo+bestaudio/best", format(&policy(None, &[])));
+    }
+
+    #[test]
+    fn codecs() {
+        assert_eq!(
+            "bestvideo[height<=720][vcodec^=vp9]+bestaudio/\
+             bestvideo[height<=720][vcodec^=avc1]+bestaudio/\
+             bestvideo[height<=720]+bestaudio/best[height<=720]/bestvideo+bestaudio/best",
+            format(&policy(Some(720), &["vp9", "avc1"]))
+        );
+    }
+
+    #[test]
+    fn qualities() {
+        let conf = policy(Some(1080), &["vp9"]);
+        assert_eq!(conf, with_quality(&conf, &Quality::Default));
+
+        let low = with_quality(&conf, &Quality::MaxHeight(480));
+        assert_eq!(Some(480), low.max_height);
+        assert_eq!(conf.codecs, low.codecs);
+
+        let audio = with_quality(&conf, &Quality::AudioOnly);
+        assert_eq!("bestaudio/best", format(&audio));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::VecDeque, ffi::OsString, path::Path};
+
+use protocol::{
+    to_client::front::mpv::queue as prot,
+    to_server::mpvstart::{self, options::MpvOptions, url::Quality},
+};
+
+/// Things to play in mpv after the currently playing one, in order.
+#[derive(Debug, Default)]
+pub struct PlayQueue {
+    entries: VecDeque<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    File {
+        root: usize,
+        path: OsString,
+        options: MpvOptions,
+    },
+    Url {
+        url: String,
+        quality: Quality,
+        options: MpvOptions,
+    },
+}
+
+impl From<mpvstart::url::Url> for Entry {
+    fn from(url: mpvstart::url::Url) -> Self {
+        Self::Url {
+            url: url.url,
+            quality: url.quality,
+            options: url.options,
+        }
+    }
+}
+
+impl PlayQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_front()
+    }
+
+    pub fn remove(&mut self, i: usize) -> Result<(), ()> {
+        self.entries.remove(i).map(|_| ()).ok_or(())
+    }
+
+    /// Move the entry at `i` one step closer to the front of the queue.
+    pub fn move_up(&mut self, i: usize) -> Result<(), ()> {
+        if i == 0 || i >= self.entries.len() {
+            return Err(());
+        }
+        self.entries.swap(i - 1, i);
+        Ok(())
+    }
+
+    /// Move the entry at `i` one step closer to the back of the queue.
+    pub fn move_down(&mut self, i: usize) -> Result<(), ()> {
+        if i >= self.entries.len().saturating_sub(1) {
+            return Err(());
+        }
+        self.entries.swap(i, i + 1);
+        Ok(())
+    }
+
+    pub fn to_client(&self) -> prot::Queue {
+        prot::Queue {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| match entry {
+                    Entry::File { root, path, .. } => prot::Entry::File {
+                        root: *root,
+                        name: Path::new(path)
+                            .file_name()
+                            .unwrap_or(path)
+                            .to_string_lossy()
+                            .into_owned(),
+                    },
+                    Entry::Url { url, .. } => prot::Entry::Url { url: url.clone() },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url(url: &str) -> Entry {
+        Entry::Url {
+            url: url.to_string(),
+            quality: Quality::Default,
+            options: MpvOptions::new(),
+        }
+    }
+
+    fn queue(urls: &[&str]) -> PlayQueue {
+        let mut queue = PlayQueue::new();
+        urls.iter().for_each(|u| queue.push(url(u)));
+        queue
+    }
+
+    fn drain(mut queue: PlayQueue) -> Vec<Entry> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn pops_in_order() {
+        assert_eq!(vec![url("a"), url("b")], drain(queue(&["a", "b"])));
+    }
+
+    #[test]
+    fn moving() {
+        let mut q = queue(&["a", "b", "c"]);
+        assert_eq!(Ok(()), q.move_up(2));
+        assert_eq!(Ok(()), q.move_down(0));
+        assert_eq!(vec![url("c"), url("a"), url("b")], drain(q));
+    }
+
+    #[test]
+    fn moving_out_of_bounds() {
+        let mut q = queue(&["a", "b"]);
+        assert_eq!(Err(()), q.move_up(0));
+        assert_eq!(Err(()), q.move_up(2));
+        assert_eq!(Err(()), q.move_down(1));
+        assert_eq!(Err(()), q.move_down(usize::MAX));
+        assert_eq!(vec![url("a"), url("b")], drain(q));
+    }
+
+    #[test]
+    fn removing() {
+        let mut q = queue(&["a", "b", "c"]);
+        assert_eq!(Ok(()), q.remove(1));
+        assert_eq!(Err(()), q.remove(2));
+        assert_eq!(vec![url("a"), url("c")], drain(q));
+    }
+}
@@ -3,47 +3,34 @@ use std::future::Future;
 use std::process::ExitStatus;
 
 use protocol::{
-    to_client::{front::Front, ToClient},
+    to_client::front::Front,
     to_server::{
-        mpvstart::{self, MpvStart},
+        mpvstart::{self, options::MpvOptions, MpvStart},
+        previewreq::PreviewReq,
         ToServer,
     },
+    util::FileId,
     ToClientable,
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::{caster, util::FutureCancel};
+use crate::{
+    caster::{self, ClientId, ToConn},
+    filer::shared::SharedCache,
+    mpv::thumbnail,
+    util::{absolute, FutureCancel},
+};
 
 mod init_state;
 
 pub type MachineResult<T> = anyhow::Result<T>;
 
-struct Gatekeeper {
-    last_sent: Front,
-}
-
-impl Gatekeeper {
-    fn new(initial_state: Front) -> Self {
-        Self {
-            last_sent: initial_state,
-        }
-    }
-
-    fn last_sent(&self) -> Front {
-        self.last_sent.clone()
-    }
-
-    fn set_last_sent(&mut self, msg: &ToClient) {
-        if let ToClient::Front(f) = msg {
-            self.last_sent = f.clone();
-        }
-    }
-}
-
 struct Control {
     from_conn: caster::Receiver,
     to_conn: caster::Sender,
-    keeper: Gatekeeper,
+    keeper: caster::Gatekeeper,
+    thumbnailer: thumbnail::Sender,
+    cache: SharedCache,
     canceltoken: CancellationToken,
 }
 
@@ -52,12 +39,16 @@ impl Control {
         from_conn: caster::Receiver,
         to_conn: caster::Sender,
         initial_state: Front,
+        thumbnailer: thumbnail::Sender,
+        cache: SharedCache,
         canceltoken: CancellationToken,
     ) -> Self {
         Self {
             from_conn,
             to_conn,
-            keeper: Gatekeeper::new(initial_state),
+            keeper: caster::Gatekeeper::new(initial_state),
+            thumbnailer,
+            cache,
             canceltoken,
         }
     }
@@ -65,13 +56,13 @@ impl Control {
     async fn send(&mut self, msg: impl ToClientable) {
         let m = msg.to_client();
         self.keeper.set_last_sent(&m);
-        if self.to_conn.send(m).await.is_err() {
+        if self.to_conn.send(ToConn::All(m)).await.is_err() {
             log::warn!("Seems like connections is down");
         }
     }
 
     async fn recv(&mut self) -> Option<ToServer> {
-        while let Some(Some(toserver)) =
+        while let Some(Some((client, toserver))) =
             self.from_conn.recv().cancellable(&self.canceltoken).await
         {
             if let ToServer::SendStatus(_) = toserver {
@@ -80,6 +71,16 @@ impl Control {
                 continue;
             }
 
+            if let ToServer::PreviewReq(req) = toserver {
+                match req {
+                    PreviewReq::Thumbnails(ids) => self.request_thumbnails(client, ids),
+                    PreviewReq::Sprites => {
+                        self.request_thumbnail(thumbnail::Request::Sprites { client })
+                    }
+                }
+                continue;
+            }
+
             return Some(toserver);
         }
 
@@ -87,6 +88,31 @@ impl Control {
         None
     }
 
+    /// Previews can be shown in any state, so they are handled here instead
+    fn request_thumbnails(&self, client: ClientId, ids: Vec<FileId>) {
+        let cache = self.cache.get();
+        let videos = ids
+            .into_iter()
+            .filter_map(|id| match cache.resolve(id) {
+                Ok((root, path)) => {
+                    Some((id, absolute(&cache.roots_path()[root], path).into()))
+                }
+                Err(_) => {
+                    log::debug!("Not making a thumbnail of an unknown file: {id:?}");
+                    None
+                }
+            })
+            .collect();
+        self.request_thumbnail(thumbnail::Request::Thumbnails { client, videos });
+    }
+
+    /// Ask the thumbnailer for something, without waiting for it to have room
+    fn request_thumbnail(&self, request: thumbnail::Request) {
+        if let Err(e) = self.thumbnailer.try_send(request) {
+            log::warn!("Could not ask the thumbnailer: {e}");
+        }
+    }
+
     async fn send_recv(&mut self, msg: impl ToClientable) -> Option<ToServer> {
         self.send(msg).await;
         self.recv().await
@@ -182,14 +208,26 @@ impl Jump {
         .into())
     }
 
-    fn mpv_file<T>(root: usize, path: String) -> MachineResult<T> {
-        log::debug!("Jump to mpv: root={root}, path={path}");
-        Err(Self::Mpv(mpvstart::file::File { root, path }.into()).into())
+    fn mpv_file<T>(id: FileId, options: MpvOptions) -> MachineResult<T> {
+        log::debug!("Jump to mpv: id={id:?}, options={options:?}");
+        Err(Self::Mpv(mpvstart::file::File { id, options }.into()).into())
     }
 
-    fn mpv_url<T>(url: String, paused: bool) -> MachineResult<T> {
-        log::debug!("Jump to mpv: url={url}, paused={paused}");
-        Err(Self::Mpv(mpvstart::url::Url { url, paused }.into()).into())
+    fn mpv_url<T>(
+        url: String,
+        quality: mpvstart::url::Quality,
+        options: MpvOptions,
+    ) -> MachineResult<T> {
+        log::debug!("Jump to mpv: url={url}, quality={quality:?}, options={options:?}");
+        Err(Self::Mpv(
+            mpvstart::url::Url {
+                url,
+                quality,
+                options,
+            }
+            .into(),
+        )
+        .into())
     }
 }
 
@@ -280,8 +318,17 @@ impl Drop for StateLogger<'_> {
 pub async fn state_start(
     from_conn: caster::Receiver,
     to_conn: caster::Sender,
+    thumbnailer: thumbnail::Sender,
+    cache: SharedCache,
     canceltoken: CancellationToken,
 ) -> MachineResult<()> {
-    let mut ctrl = Control::new(from_conn, to_conn, Front::None, canceltoken);
+    let mut ctrl = Control::new(
+        from_conn,
+        to_conn,
+        Front::None,
+        thumbnailer,
+        cache,
+        canceltoken,
+    );
     init_state::init_state(&mut ctrl).await
 }
@@ -6,6 +6,7 @@ use protocol::{
 };
 
 use crate::process::Process;
+use crate::queue::PlayQueue;
 
 use self::mpv_state::{mpv_file_state, mpv_url_state};
 use self::play_url_state::play_url_state;
@@ -22,6 +23,7 @@ mod spotify_state;
 pub(super) async fn init_state(ctrl: &mut Control) -> MachineResult<()> {
     let logger = StateLogger::new("Init");
     let mut queue = InjectableQueue::new();
+    let mut playqueue = PlayQueue::new();
 
     while let Some(msg) = queue.pop_or(|| ctrl.send_recv(Front::None)).await {
         let res: MachineResult<()> = match msg {
@@ -36,11 +38,15 @@ pub(super) async fn init_state(ctrl: &mut Control) -> MachineResult<()> {
                         ()
                     })
             }
-            ToServer::MpvStart(mpvstart::Url(mpvstart::url::Url { url, paused })) => {
-                mpv_url_state(ctrl, url, paused).await.context("mpv url")
-            }
+            ToServer::MpvStart(mpvstart::Url(mpvstart::url::Url {
+                url,
+                quality,
+                options,
+            })) => mpv_url_state(ctrl, &mut playqueue, url, quality, options)
+                .await
+                .context("mpv url"),
             ToServer::MpvStart(mpvstart::File(file)) => {
-                mpv_file_state(ctrl, file.root, file.path)
+                mpv_file_state(ctrl, &mut playqueue, file.id, file.options)
                     .await
                     .context("mpv file")
             }
@@ -48,10 +54,14 @@ pub(super) async fn init_state(ctrl: &mut Control) -> MachineResult<()> {
                 spotify_state(ctrl).await.context("spotify")
             }
             ToServer::FsStart(fsstart::Start) => {
-                filer_state::filer_state(ctrl).await.context("filer")
+                filer_state::filer_state(ctrl, &mut playqueue)
+                    .await
+                    .context("filer")
             }
             ToServer::PlayUrlStart(playurlstart::Start) => {
-                play_url_state(ctrl).await.context("play url")
+                play_url_state(ctrl, &mut playqueue)
+                    .await
+                    .context("play url")
             }
             _ => {
                 logger.invalid_message(&msg);
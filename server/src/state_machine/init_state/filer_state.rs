@@ -1,26 +1,42 @@
+use std::{mem, sync::Arc};
+
 use anyhow::Context;
 use protocol::{
     to_client::front::filesearch,
     to_client::front::filesearch::tree as prot_tree,
     to_server::{
-        fscontrol::{self, search_ctrl, tree_ctrl},
-        fsstart, mpvstart, ToServer,
+        fscontrol::{self, search_ctrl, tree_ctrl, watched_ctrl},
+        fsstart, mpvstart, queuectrl, ToServer,
     },
 };
 
-use crate::filer::{
-    self, cache::Cache, cache_file, read_cache, refresh_cache, tree::Tree,
+use crate::{
+    filer::{self, cache::Cache, tree::Tree},
+    mpv::probe::Media,
+    queue::{self, PlayQueue},
+    watch_later::{watch_later_file, WatchLater},
+    watched::{watched_file, Watched},
 };
 
-use super::{Control, Jump, LockedControl, MachineResult, StateLogger};
+use super::{Control, Jump, JumpableError, LockedControl, MachineResult, StateLogger};
 
-pub(super) async fn filer_state(ctrl: &mut Control) -> MachineResult<()> {
+pub(super) async fn filer_state(
+    ctrl: &mut Control,
+    queue: &mut PlayQueue,
+) -> MachineResult<()> {
     let logger = StateLogger::new("Filer");
-    let mut cache = filer_read_cache_state(ctrl).await?;
+    let mut cache = ctrl.cache.get();
+    let mut watched = Watched::read(&watched_file())
+        .await
+        .context("reading the watched files")?;
+    let watch_later = WatchLater::read(&watch_later_file())
+        .await
+        .context("reading the saved playback positions")?;
 
     while let Some(msg) = ctrl
         .send_recv_lazy(|| filesearch::init::Init {
             last_cache_date: cache.updated(),
+            live: filer::watcher::is_live(),
         })
         .await
     {
@@ -32,12 +48,14 @@ pub(super) async fn filer_state(ctrl: &mut Control) -> MachineResult<()> {
                     .context("filer refresh cache state")?;
             }
             ToServer::FsStart(fsstart::Search) => {
-                filer_search_state(ctrl, &cache)
+                cache = ctrl.cache.get();
+                filer_search_state(ctrl, &cache, queue, &mut watched, &watch_later)
                     .await
                     .context("filer search state")?;
             }
             ToServer::FsStart(fsstart::Tree) => {
-                filer_tree_state(ctrl, &cache)
+                cache = ctrl.cache.get();
+                filer_tree_state(ctrl, &mut cache, queue, &mut watched, &watch_later)
                     .await
                     .context("filer tree state")?;
             }
@@ -48,27 +66,12 @@ pub(super) async fn filer_state(ctrl: &mut Control) -> MachineResult<()> {
     Ok(())
 }
 
-async fn filer_read_cache_state(ctrl: &mut Control) -> MachineResult<Cache> {
-    let _logger = StateLogger::new("FilerReadCache");
-
-    // TODO: flag to say it is initializing/loading
-    ctrl.send(filesearch::init::Init {
-        last_cache_date: None,
-    })
-    .await;
-
-    let cache = read_cache(&cache_file())
-        .await
-        .context("failed to read the cache");
-
-    Ok(cache?)
-}
-
-async fn filer_refresh_cache_state(ctrl: &mut Control) -> MachineResult<Cache> {
+async fn filer_refresh_cache_state(ctrl: &mut Control) -> MachineResult<Arc<Cache>> {
     let logger = StateLogger::new("FilerRefreshCache");
 
+    let shared = ctrl.cache.clone();
     let ctrl = LockedControl::new(ctrl);
-    let cache = refresh_cache(|state| ctrl.send(state)).await?;
+    let cache = shared.refresh(|state| ctrl.send(state)).await?;
     let ctrl = ctrl.into_inner();
 
     while let Some(msg) = ctrl.recv().await {
@@ -81,20 +84,59 @@ async fn filer_refresh_cache_state(ctrl: &mut Control) -> MachineResult<Cache> {
     Ok(cache)
 }
 
-async fn filer_search_state(ctrl: &mut Control, cache: &Cache) -> MachineResult<()> {
+async fn filer_search_state(
+    ctrl: &mut Control,
+    cache: &Cache,
+    queue: &mut PlayQueue,
+    watched: &mut Watched,
+    watch_later: &WatchLater,
+) -> MachineResult<()> {
     let logger = StateLogger::new("FilerSearch");
+    let mut search = search_ctrl::search::Search {
+        query: "".to_string(),
+        mode: Default::default(),
+    };
 
-    ctrl.send(filer::search::search("".to_string(), cache))
-        .await;
+    ctrl.send(filer::search::search(
+        search.clone(),
+        cache,
+        watched,
+        watch_later,
+    ))
+    .await;
 
     while let Some(msg) = ctrl.recv().await {
         match msg {
             ToServer::FsStart(fsstart::Stop) => break,
-            ToServer::FsControl(fscontrol::SearchCtrl(search_ctrl::Search(search))) => {
-                ctrl.send(filer::search::search(search, cache)).await;
+            ToServer::FsControl(fscontrol::SearchCtrl(search_ctrl::Search(new))) => {
+                search = new;
+                ctrl.send(filer::search::search(
+                    search.clone(),
+                    cache,
+                    watched,
+                    watch_later,
+                ))
+                .await;
+            }
+            ToServer::FsControl(fscontrol::WatchedCtrl(watched_ctrl::Toggle(file))) => {
+                toggle_watched(cache, watched, file).await?;
+                ctrl.send(filer::search::search(
+                    search.clone(),
+                    cache,
+                    watched,
+                    watch_later,
+                ))
+                .await;
             }
             ToServer::MpvStart(mpvstart::File(file)) => {
-                return Jump::mpv_file(file.root, file.path);
+                let id = ctrl
+                    .cache
+                    .reissue(cache, file.id)
+                    .jump_user_error("Could not find the file to play")?;
+                return Jump::mpv_file(id, file.options);
+            }
+            ToServer::QueueCtrl(queuectrl::AddFile(file)) => {
+                enqueue_file(cache, queue, file, &logger);
             }
             m => logger.invalid_message(&m),
         }
@@ -103,11 +145,20 @@ async fn filer_search_state(ctrl: &mut Control, cache: &Cache) -> MachineResult<
     Ok(())
 }
 
-async fn filer_tree_state(ctrl: &mut Control, cache: &Cache) -> MachineResult<()> {
+async fn filer_tree_state(
+    ctrl: &mut Control,
+    cache: &mut Arc<Cache>,
+    queue: &mut PlayQueue,
+    watched: &mut Watched,
+    watch_later: &WatchLater,
+) -> MachineResult<()> {
     let logger = StateLogger::new("FilerTree");
     let mut tree = Tree::new(cache);
 
-    while let Some(msg) = ctrl.send_recv(create_tree_state(&tree)).await {
+    while let Some(msg) = ctrl
+        .send_recv(create_tree_state(&tree, cache, watched, watch_later))
+        .await
+    {
         match msg {
             ToServer::FsStart(fsstart::Stop) => break,
             ToServer::FsControl(fscontrol::TreeCtrl(tree_ctrl::Cd(i))) => {
@@ -120,8 +171,31 @@ async fn filer_tree_state(ctrl: &mut Control, cache: &Cache) -> MachineResult<()
                     logger.warn("can't cd up, already at the top");
                 }
             }
+            ToServer::FsControl(fscontrol::TreeCtrl(tree_ctrl::Refresh)) => {
+                let Some(location) = tree.location() else {
+                    logger.warn("can't refresh, not in any directory");
+                    continue;
+                };
+                drop(tree);
+                *cache = ctrl
+                    .cache
+                    .refresh_subtree(location.root, location.current_dir())
+                    .await
+                    .jump_user_error("Failed to refresh the directory")?;
+                tree = Tree::with_location(cache, &location);
+            }
+            ToServer::FsControl(fscontrol::WatchedCtrl(watched_ctrl::Toggle(file))) => {
+                toggle_watched(cache, watched, file).await?;
+            }
             ToServer::MpvStart(mpvstart::File(file)) => {
-                return Jump::mpv_file(file.root, file.path);
+                let id = ctrl
+                    .cache
+                    .reissue(cache, file.id)
+                    .jump_user_error("Could not find the file to play")?;
+                return Jump::mpv_file(id, file.options);
+            }
+            ToServer::QueueCtrl(queuectrl::AddFile(file)) => {
+                enqueue_file(cache, queue, file, &logger);
             }
             m => logger.invalid_message(&m),
         }
@@ -130,7 +204,54 @@ async fn filer_tree_state(ctrl: &mut Control, cache: &Cache) -> MachineResult<()
     Ok(())
 }
 
-fn create_tree_state(tree: &Tree) -> prot_tree::Tree {
+fn enqueue_file(
+    cache: &Cache,
+    queue: &mut PlayQueue,
+    file: mpvstart::file::File,
+    logger: &StateLogger,
+) {
+    match cache.resolve(file.id) {
+        Ok((root, path)) => {
+            logger.info(format!("queueing root={root}, path={path:?}"));
+            queue.push(queue::Entry::File {
+                root,
+                path: path.to_owned(),
+                options: file.options,
+            });
+        }
+        Err(e) => logger.warn(format!("can't queue {:?}: {}", file.id, e)),
+    }
+}
+
+/// Flip the watched state of a file and save it
+async fn toggle_watched(
+    cache: &Cache,
+    watched: &mut Watched,
+    file: mpvstart::file::File,
+) -> MachineResult<()> {
+    let (root, path) = match cache.resolve(file.id) {
+        Ok(found) => found,
+        Err(e) => {
+            log::error!("Can't toggle watched of {:?}: {}", file.id, e);
+            return Ok(());
+        }
+    };
+
+    watched.toggle(&cache.roots_path()[root], path);
+    *watched = mem::take(watched)
+        .write(&watched_file())
+        .await
+        .context("writing the watched files")?;
+
+    Ok(())
+}
+
+fn create_tree_state(
+    tree: &Tree,
+    cache: &Cache,
+    watched: &Watched,
+    watch_later: &WatchLater,
+) -> prot_tree::Tree {
     prot_tree::Tree {
         breadcrumbs: tree.breadcrumbs(),
         contents: tree
@@ -140,14 +261,21 @@ fn create_tree_state(tree: &Tree) -> prot_tree::Tree {
                     name,
                     path_relative_root,
                     ty: filer::tree::Type::Regular,
-                    ..
-                } => prot_tree::Entry::File {
-                    path: path_relative_root.to_string(),
-                    root: tree
+                    id,
+                    media,
+                } => {
+                    let root = tree
                         .root()
-                        .expect("this is non-None if there are files available"),
-                    name: name.to_string(),
-                },
+                        .expect("this is non-None if there are files available");
+                    let root_path = &cache.roots_path()[root];
+                    prot_tree::Entry::File {
+                        id: cache.file_id(id),
+                        name: name.to_string(),
+                        watched: watched.is_watched(root_path, path_relative_root),
+                        progress: watch_later.progress(root_path, path_relative_root),
+                        media: media.map(Media::to_client),
+                    }
+                }
                 filer::tree::File {
                     name,
                     ty: filer::tree::Type::Directory,
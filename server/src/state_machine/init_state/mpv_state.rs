@@ -1,62 +1,244 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use anyhow::Context;
+use libmpv::EndReason;
 use protocol::{
     to_client::front,
-    to_server::{mpvstart, ToServer},
+    to_server::{
+        mpvstart::{
+            self,
+            options::{MpvOptions, SubFile},
+            url::Quality,
+        },
+        queuectrl::QueueCtrl,
+        resumectrl::ResumeCtrl,
+        ToServer,
+    },
+    util::FileId,
+};
+use tokio::{
+    select,
+    task::spawn_blocking,
+    time::{sleep_until, Instant},
 };
-use tokio::select;
 
 use crate::{
-    filer::{cache_file, read_cache},
-    mpv::{self},
+    config,
+    filer::shared::SharedCache,
+    mpv::{
+        self,
+        sidecar::Sidecar,
+        skip::{self, Markers, Skipper},
+        thumbnail, Setup,
+    },
+    queue::{self, PlayQueue},
+    util::{absolute, join_handle_wait_take},
+    watch_later::{is_finished, watch_later_file, WatchLater},
+    watched::{watched_file, Watched},
 };
 
-use super::{Control, Jump, MachineResult, StateLogger};
+use super::{Control, Jump, JumpableError, MachineResult, StateLogger};
+
+use self::{
+    patcher::Patcher,
+    throttle::{is_urgent, Throttle},
+};
+
+mod patcher;
+mod throttle;
+
+/// How the playback in [`mpv_state`] ended
+struct Played {
+    reason: EndReason,
+    skip: bool,
+    last_state: front::mpv::Mpv,
+}
 
 pub(super) async fn mpv_url_state(
     ctrl: &mut Control,
+    queue: &mut PlayQueue,
     url: String, // TODO: use an URL type
-    paused: bool,
+    quality: Quality,
+    options: MpvOptions,
 ) -> MachineResult<()> {
     let logger = StateLogger::new("MpvUrl");
-    logger.info(format!("Playing URL: url={url}, paused={paused}"));
-    mpv_state(ctrl, url, paused).await
+    logger.info(format!("Playing URL: url={url}, quality={quality:?}"));
+    let setup = Setup {
+        languages: config::languages(None),
+        ytdl: Some(mpv::ytdl::with_quality(config::ytdl_policy(), &quality)),
+        sidecars: Vec::new(),
+        skipper: Skipper::new(config::skip_conf(), Markers::default(), false),
+    };
+    let played = mpv_state(ctrl, queue, OsStr::new(&url), options, setup).await?;
+    play_next(&ctrl.cache, queue, &played)
 }
 
 pub(super) async fn mpv_file_state(
     ctrl: &mut Control,
-    root: usize,
-    path: String,
+    queue: &mut PlayQueue,
+    id: FileId,
+    mut options: MpvOptions,
 ) -> MachineResult<()> {
     let logger = StateLogger::new("MpvFile");
-    logger.info(format!("Playing file: root={root}, path={path}"));
 
-    // TODO: the whole cache is re-read over and over, so cache the cache somehow? Maybe
-    // save the roots in another file?
-    let cache = read_cache(&cache_file())
+    let cache = ctrl.cache.get();
+    let (root, path) = cache
+        .resolve(id)
+        .jump_user_error("Could not find the file to play")?;
+    logger.info(format!("Playing file: root={root}, path={path:?}"));
+    let r = &cache.roots_path()[root];
+    assert!(!r.ends_with('/'));
+
+    let watch_later_file = watch_later_file();
+    let mut watch_later = WatchLater::read(&watch_later_file)
         .await
-        .context("failed to read the cache")?;
-    let roots = cache.roots_path();
+        .context("reading the saved playback positions")?;
 
-    match roots.get(root) {
-        None => {
-            logger.error(format!("Root {} out of range of 0..{}", root, roots.len()));
-            Jump::user_error("Could not find file to play", "Root dir is out of range")
+    if options.start.is_none() {
+        if let Some(position) = watch_later.position(r, path) {
+            match resume_state(ctrl, path, position).await {
+                Some(start) => options.start = Some(start),
+                None => return Ok(()),
+            }
         }
-        Some(r) => {
-            assert!(path.starts_with('/'));
-            assert!(!r.ends_with('/'));
-            mpv_state(ctrl, r.to_string() + &path, false).await
+    }
+
+    let abs_path = absolute(r, path);
+    let video = PathBuf::from(&abs_path);
+    let markers = skip::read_markers(&video, config::skip_conf()).await;
+    ctrl.request_thumbnail(thumbnail::Request::Play {
+        video: video.clone(),
+    });
+    let sidecars =
+        join_handle_wait_take(spawn_blocking(move || mpv::sidecar::find(&video))).await;
+
+    let setup = Setup {
+        languages: config::languages(Some(r)),
+        ytdl: None,
+        sidecars,
+        skipper: Skipper::new(config::skip_conf(), markers, config::auto_skip(Some(r))),
+    };
+    let played = mpv_state(ctrl, queue, &abs_path, options, setup).await;
+    ctrl.request_thumbnail(thumbnail::Request::Stop);
+    let played = played?;
+
+    let finished = matches!(played.reason, EndReason::EOF)
+        || matches!(&played.last_state, front::mpv::PlayState(state)
+                    if is_finished(state.progress, state.length));
+
+    if finished {
+        watch_later.remove(r, path);
+        let watched_file = watched_file();
+        let mut watched = Watched::read(&watched_file)
+            .await
+            .context("reading the watched files")?;
+        watched.set_watched(r, path, true);
+        watched
+            .write(&watched_file)
+            .await
+            .context("writing the watched files")?;
+    } else if let front::mpv::PlayState(state) = &played.last_state {
+        watch_later.update(r, path, state.progress, state.length);
+    }
+    watch_later
+        .write(&watch_later_file)
+        .await
+        .context("writing the saved playback positions")?;
+
+    play_next(&ctrl.cache, queue, &played)
+}
+
+/// Ask if the file should continue from `position` or start over. Returns where to
+/// start, or `None` if it should not be played after all.
+async fn resume_state(
+    ctrl: &mut Control,
+    path: &OsStr,
+    position: Duration,
+) -> Option<Duration> {
+    let logger = StateLogger::new("MpvResume");
+    let state = front::mpv::resume::Resume {
+        name: Path::new(path)
+            .file_name()
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned(),
+        position,
+    };
+
+    while let Some(msg) = ctrl.send_recv(state.clone()).await {
+        match msg {
+            ToServer::ResumeCtrl(ResumeCtrl::Resume) => return Some(position),
+            ToServer::ResumeCtrl(ResumeCtrl::StartOver) => return Some(Duration::ZERO),
+            ToServer::MpvStart(mpvstart::Stop) => return None,
+            _ => logger.invalid_message(&msg),
         }
     }
+
+    None
 }
 
-async fn mpv_state(ctrl: &mut Control, path: String, paused: bool) -> MachineResult<()> {
+/// Continue with the next thing in the queue if the last one finished or was skipped
+fn play_next(
+    cache: &SharedCache,
+    queue: &mut PlayQueue,
+    played: &Played,
+) -> MachineResult<()> {
+    if !played.skip && !matches!(played.reason, EndReason::EOF) {
+        return Ok(());
+    }
+
+    let Some(next) = queue.pop() else {
+        return Ok(());
+    };
+
+    log::info!("Playing the next thing in the queue: {next:?}");
+    match next {
+        queue::Entry::Url {
+            url,
+            quality,
+            options,
+        } => Jump::mpv_url(url, quality, options),
+        queue::Entry::File {
+            root,
+            path,
+            options,
+        } => {
+            let cache = cache.get();
+            match cache.find_file(root, &path) {
+                Some(i) => Jump::mpv_file(cache.file_id(i), options),
+                None => Jump::user_error(
+                    "Could not play the next file in the queue",
+                    format!("{path:?} is not among the files anymore"),
+                ),
+            }
+        }
+    }
+}
+
+async fn mpv_state(
+    ctrl: &mut Control,
+    queue: &mut PlayQueue,
+    path: &OsStr,
+    options: MpvOptions,
+    mut setup: Setup,
+) -> MachineResult<Played> {
     let logger = StateLogger::new("Mpv");
-    logger.debug(format!("path={path}, paused={paused}"));
+    logger.debug(format!("path={path:?}, options={options:?}"));
+    setup.sidecars.extend(requested_subs(&options.sub_files));
 
-    ctrl.send(front::mpv::Load).await;
+    let mut last_state = front::mpv::Load;
+    let mut sent_state = last_state.clone();
+    let mut throttle = Throttle::new(config::update_interval(), config::update_batch());
+    let mut patcher = Patcher::default();
+    let mut show_queue = false;
+    let mut skip = false;
+    send_state(ctrl, &mut patcher, &last_state).await;
 
-    let mut handle = mpv::mpv(&path, paused).context("creating mpv handle")?;
+    let mut handle = mpv::mpv(path, &options, setup).context("creating mpv handle")?;
 
     let retval: MachineResult<()> = loop {
         select! {
@@ -69,6 +251,26 @@ async fn mpv_state(ctrl: &mut Control, path: String, paused: bool) -> MachineRes
                         handle.command(mpvctrl.clone())
                             .with_context(|| format!("calling command {:?}", mpvctrl))
                     },
+                    Some(ToServer::QueueCtrl(QueueCtrl::Skip)) => {
+                        skip = true;
+                        break Ok(())
+                    },
+                    Some(ToServer::QueueCtrl(QueueCtrl::Show)) => {
+                        show_queue = true;
+                        ctrl.send(queue.to_client()).await;
+                    },
+                    Some(ToServer::QueueCtrl(QueueCtrl::Hide)) => {
+                        show_queue = false;
+                        send_state(ctrl, &mut patcher, &last_state).await;
+                    },
+                    Some(ToServer::QueueCtrl(queuectrl)) => {
+                        if let Err(()) = queue_command(queue, queuectrl.clone()) {
+                            logger.warn(format!("invalid queue command: {queuectrl:?}"));
+                        }
+                        if show_queue {
+                            ctrl.send(queue.to_client()).await;
+                        }
+                    },
                     Some(m) => {
                         logger.invalid_message(&m);
                     },
@@ -77,17 +279,84 @@ async fn mpv_state(ctrl: &mut Control, path: String, paused: bool) -> MachineRes
             state = handle.next() => {
                 match state {
                     Some(Ok(newstate)) => {
-                        ctrl.send(newstate).await;
+                        let urgent = is_urgent(&sent_state, &newstate);
+                        last_state = newstate;
+                        if throttle.changed(urgent, Instant::now()) {
+                            throttle.sent(Instant::now());
+                            sent_state = last_state.clone();
+                            if !show_queue {
+                                send_state(ctrl, &mut patcher, &last_state).await;
+                            }
+                        }
                     },
                     None => break Ok(()),
                     Some(Err(e)) => break Jump::user_error("Mpv play", e),
                 }
             }
+            _ = sleep_until(throttle.deadline().unwrap_or_else(Instant::now)),
+                if throttle.deadline().is_some() => {
+                throttle.sent(Instant::now());
+                sent_state = last_state.clone();
+                if !show_queue {
+                    send_state(ctrl, &mut patcher, &last_state).await;
+                }
+            }
         }
     };
 
     logger.waiting("mpv handle to exit");
     let reason = handle.wait_until_closed().await;
     logger.debug(format!("exit reason: {reason:?}"));
-    retval
+    retval?;
+
+    Ok(Played {
+        reason,
+        skip,
+        last_state,
+    })
+}
+
+/// The subtitle files asked for, leaving out the ones that can't be loaded
+fn requested_subs(sub_files: &[SubFile]) -> Vec<Sidecar> {
+    sub_files
+        .iter()
+        .filter_map(|sub_file| match sub_file {
+            SubFile::Url(url)
+                if url.starts_with("http://") || url.starts_with("https://") =>
+            {
+                Some(PathBuf::from(url))
+            }
+            SubFile::Url(url) => {
+                log::warn!(
+                    "Not loading subtitles from {url:?}, it is not an http(s) URL"
+                );
+                None
+            }
+        })
+        .map(|path| Sidecar {
+            path,
+            title: None,
+            lang: None,
+        })
+        .collect()
+}
+
+/// Sends `state`, or only what changed if the clients already have a full one
+async fn send_state(ctrl: &mut Control, patcher: &mut Patcher, state: &front::mpv::Mpv) {
+    if let Some(msg) = patcher.update(state, ctrl.keeper.has_playstate()) {
+        ctrl.send(msg).await;
+    }
+}
+
+fn queue_command(queue: &mut PlayQueue, cmd: QueueCtrl) -> Result<(), ()> {
+    match cmd {
+        // NOTE: files can only be added from the filer, since the cache is needed
+        QueueCtrl::AddFile(_) => return Err(()),
+        QueueCtrl::AddUrl(url) => queue.push(url.into()),
+        QueueCtrl::Remove(i) => queue.remove(i)?,
+        QueueCtrl::MoveUp(i) => queue.move_up(i)?,
+        QueueCtrl::MoveDown(i) => queue.move_down(i)?,
+        QueueCtrl::Show | QueueCtrl::Hide | QueueCtrl::Skip => return Err(()),
+    }
+    Ok(())
 }
@@ -0,0 +1,159 @@
+use protocol::to_client::front::mpv::{playstate::PlayState, Mpv};
+
+/// Turns the play states into patches against the last state that was sent, so that a
+/// progress tick doesn't have to carry all the tracks with it. Every state gets the next
+/// seq, so that the clients can tell when they have missed a patch.
+#[derive(Debug, Default)]
+pub struct Patcher {
+    /// The last state that was sent, with all the patches after it applied
+    last: Option<PlayState>,
+    seq: u64,
+}
+
+impl Patcher {
+    /// What to send to the clients for `state`, `None` if they already have it.
+    /// `has_playstate` tells if the clients have a full state to apply a patch to.
+    pub fn update(&mut self, state: &Mpv, has_playstate: bool) -> Option<Mpv> {
+        match (state, &mut self.last) {
+            (Mpv::PlayState(new), Some(last)) if has_playstate => {
+                let mut patch = last.diff(new);
+                if patch.is_empty() {
+                    return None;
+                }
+                self.seq += 1;
+                patch.seq = self.seq;
+                last.apply(patch.clone());
+                Some(Mpv::PlayStatePatch(patch))
+            }
+            (Mpv::PlayState(new), _) => {
+                self.seq += 1;
+                let new = PlayState {
+                    seq: self.seq,
+                    ..new.clone()
+                };
+                self.last = Some(new.clone());
+                Some(Mpv::PlayState(new))
+            }
+            _ => {
+                self.last = None;
+                Some(state.clone())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use protocol::to_client::front::mpv::playstate::{Chapter, Patch, Track};
+
+    use super::*;
+
+    fn playstate(progress: u64) -> PlayState {
+        PlayState {
+            seq: 0,
+            title: "title".to_string(),
+            pause: false,
+            progress: Duration::from_secs(progress),
+            length: Duration::from_secs(100),
+            volume: None,
+            chapter: None,
+            chapters: Vec::new(),
+            subtitles: Vec::new(),
+            audios: Vec::new(),
+            tweaks: Default::default(),
+            skip: None,
+        }
+    }
+
+    fn with_seq(state: PlayState, seq: u64) -> Mpv {
+        Mpv::PlayState(PlayState { seq, ..state })
+    }
+
+    #[test]
+    fn full_state_first() {
+        let mut patcher = Patcher::default();
+        let state = Mpv::PlayState(playstate(1));
+        assert_eq!(
+            Some(with_seq(playstate(1), 1)),
+            patcher.update(&state, false)
+        );
+        assert_eq!(
+            Some(with_seq(playstate(1), 2)),
+            patcher.update(&state, false)
+        );
+        assert_eq!(Some(Mpv::Load), patcher.update(&Mpv::Load, true));
+        assert_eq!(
+            Some(with_seq(playstate(1), 3)),
+            patcher.update(&state, true)
+        );
+    }
+
+    #[test]
+    fn patches_after_full_state() {
+        let mut patcher = Patcher::default();
+        patcher.update(&Mpv::PlayState(playstate(1)), false);
+        assert_eq!(None, patcher.update(&Mpv::PlayState(playstate(1)), true));
+
+        let tick = Mpv::PlayStatePatch(Patch {
+            seq: 2,
+            progress: Some(Duration::from_secs(2)),
+            ..Patch::default()
+        });
+        assert_eq!(
+            Some(tick),
+            patcher.update(&Mpv::PlayState(playstate(2)), true)
+        );
+        assert_eq!(None, patcher.update(&Mpv::PlayState(playstate(2)), true));
+
+        let back = Mpv::PlayStatePatch(Patch {
+            seq: 3,
+            progress: Some(Duration::from_secs(1)),
+            ..Patch::default()
+        });
+        assert_eq!(
+            Some(back),
+            patcher.update(&Mpv::PlayState(playstate(1)), true)
+        );
+    }
+
+    #[test]
+    fn progress_tick_only_has_progress() {
+        let full = PlayState {
+            chapter: Some((1, 2)),
+            chapters: vec![Chapter {
+                title: "Intro".to_string(),
+                start: Some(Duration::ZERO),
+            }],
+            subtitles: vec![Track {
+                id: 1,
+                title: "eng".to_string(),
+                selected: true,
+            }],
+            ..playstate(1)
+        };
+        let mut patcher = Patcher::default();
+        patcher.update(&Mpv::PlayState(full.clone()), false);
+
+        let tick = Mpv::PlayStatePatch(Patch {
+            seq: 2,
+            progress: Some(Duration::from_secs(2)),
+            ..Patch::default()
+        });
+        let mut state = full.clone();
+        state.progress = Duration::from_secs(2);
+        assert_eq!(
+            Some(tick),
+            patcher.update(&Mpv::PlayState(state.clone()), true)
+        );
+
+        let tick = Mpv::PlayStatePatch(Patch {
+            seq: 3,
+            progress: Some(Duration::from_secs(3)),
+            ..Patch::default()
+        });
+        state.progress = Duration::from_secs(3);
+        assert_eq!(Some(tick), patcher.update(&Mpv::PlayState(state), true));
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use protocol::to_client::front::mpv::Mpv;
+use tokio::time::Instant;
+
+/// Limits how often the mpv state is sent to the clients. Changes are gathered for a
+/// short while and then sent together, but never more often than allowed.
+#[derive(Debug)]
+pub struct Throttle {
+    min_interval: Duration,
+    batch: Duration,
+    last_sent: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(min_interval: Duration, batch: Duration) -> Self {
+        Self {
+            min_interval,
+            batch,
+            last_sent: None,
+            deadline: None,
+        }
+    }
+
+    /// The state has changed. Returns true if it should be sent right away, otherwise it
+    /// should be sent at [`Self::deadline`].
+    pub fn changed(&mut self, urgent: bool, now: Instant) -> bool {
+        if urgent {
+            return true;
+        }
+
+        if self.deadline.is_none() {
+            let earliest = self.last_sent.map_or(now, |t| t + self.min_interval);
+            self.deadline = Some(earliest.max(now + self.batch));
+        }
+        false
+    }
+
+    /// When the pending changes should be sent, if there are any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn sent(&mut self, now: Instant) {
+        self.last_sent = Some(now);
+        self.deadline = None;
+    }
+}
+
+/// If the change from `old` to `new` is something the user is waiting to see
+pub fn is_urgent(old: &Mpv, new: &Mpv) -> bool {
+    match (old, new) {
+        (Mpv::PlayState(old), Mpv::PlayState(new)) => {
+            old.pause != new.pause
+                || old.title != new.title
+                || old.subtitles != new.subtitles
+                || old.audios != new.audios
+                || old.tweaks != new.tweaks
+                || old.skip != new.skip
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::to_client::front::mpv::playstate::{PlayState, Track};
+
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(250);
+    const BATCH: Duration = Duration::from_millis(50);
+
+    fn playstate() -> PlayState {
+        PlayState {
+            seq: 0,
+            title: "title".to_string(),
+            pause: false,
+            progress: Duration::ZERO,
+            length: Duration::from_secs(100),
+            volume: None,
+            chapter: None,
+            chapters: Vec::new(),
+            subtitles: vec![Track {
+                id: 1,
+                title: "eng".to_string(),
+                selected: false,
+            }],
+            audios: Vec::new(),
+            tweaks: Default::default(),
+            skip: None,
+        }
+    }
+
+    #[test]
+    fn urgent_is_immediate() {
+        let now = Instant::now();
+        let mut throttle = Throttle::new(INTERVAL, BATCH);
+        throttle.sent(now);
+        assert!(throttle.changed(true, now));
+        assert_eq!(None, throttle.deadline());
+    }
+
+    #[test]
+    fn batches_changes() {
+        let now = Instant::now();
+        let mut throttle = Throttle::new(INTERVAL, BATCH);
+        assert!(!throttle.changed(false, now));
+        assert_eq!(Some(now + BATCH), throttle.deadline());
+        assert!(!throttle.changed(false, now + BATCH / 2));
+        assert_eq!(Some(now + BATCH), throttle.deadline());
+    }
+
+    #[test]
+    fn limits_rate() {
+        let now = Instant::now();
+        let mut throttle = Throttle::new(INTERVAL, BATCH);
+        throttle.sent(now);
+        assert!(!throttle.changed(false, now + BATCH));
+        assert_eq!(Some(now + INTERVAL), throttle.deadline());
+
+        throttle.sent(now + INTERVAL);
+        let later = now + 10 * INTERVAL;
+        assert!(!throttle.changed(false, later));
+        assert_eq!(Some(later + BATCH), throttle.deadline());
+    }
+
+    #[test]
+    fn urgent_changes() {
+        let old = Mpv::PlayState(playstate());
+        assert!(is_urgent(&Mpv::Load, &old));
+
+        let mut new = playstate();
+        new.progress = Duration::from_secs(3);
+        new.volume = Some(Default::default());
+        assert!(!is_urgent(&old, &Mpv::PlayState(new.clone())));
+
+        new.pause = true;
+        assert!(is_urgent(&old, &Mpv::PlayState(new)));
+
+        let mut new = playstate();
+        new.subtitles[0].selected = true;
+        assert!(is_urgent(&old, &Mpv::PlayState(new)));
+    }
+}
@@ -1,17 +1,28 @@
 use protocol::{
     to_client::front::Front,
-    to_server::{mpvstart, playurlstart, ToServer},
+    to_server::{mpvstart, playurlstart, queuectrl, ToServer},
 };
 
+use crate::queue::PlayQueue;
+
 use super::{Control, Jump, MachineResult, StateLogger};
 
-pub(super) async fn play_url_state(ctrl: &mut Control) -> MachineResult<()> {
+pub(super) async fn play_url_state(
+    ctrl: &mut Control,
+    queue: &mut PlayQueue,
+) -> MachineResult<()> {
     let logger = StateLogger::new("PlayUrl");
 
     while let Some(msg) = ctrl.send_recv(Front::PlayUrl).await {
         match msg {
-            ToServer::MpvStart(mpvstart::Url(mpvstart::url::Url { url, paused })) => {
-                return Jump::mpv_url(url, paused)
+            ToServer::MpvStart(mpvstart::Url(mpvstart::url::Url {
+                url,
+                quality,
+                options,
+            })) => return Jump::mpv_url(url, quality, options),
+            ToServer::QueueCtrl(queuectrl::AddUrl(url)) => {
+                logger.info(format!("queueing url={}", url.url));
+                queue.push(url.into());
             }
             ToServer::PlayUrlStart(playurlstart::Stop) => break,
             _ => logger.invalid_message(&msg),
@@ -1,6 +1,16 @@
+use anyhow::Context;
 use async_trait::async_trait;
-use std::{future::Future, path::Path};
-use tokio::{select, task::JoinHandle};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{create_dir_all, File},
+    future::Future,
+    io,
+    path::Path,
+};
+use tokio::{
+    select,
+    task::{spawn_blocking, JoinHandle},
+};
 use tokio_util::sync::CancellationToken;
 
 #[async_trait]
@@ -43,6 +53,39 @@ pub fn join_handle_unwrap<T>(awaited_handle: Result<T, tokio::task::JoinError>)
     }
 }
 
+/// Read a bincode file, or get the default value if there is no such file yet.
+pub async fn read_bincode<T>(path: &Path) -> anyhow::Result<T>
+where
+    T: serde::de::DeserializeOwned + Default + Send + 'static,
+{
+    let path = path.to_owned();
+    join_handle_wait_take(spawn_blocking(move || match File::open(&path) {
+        Ok(file) => bincode::deserialize_from(file).context("deserializing"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("opening {}", path.display())),
+    }))
+    .await
+}
+
+/// Write `contents` as bincode to a file, and give it back when done.
+pub async fn write_bincode<T>(path: &Path, contents: T) -> anyhow::Result<T>
+where
+    T: serde::Serialize + Send + 'static,
+{
+    let path = path.to_owned();
+    join_handle_wait_take(spawn_blocking(move || {
+        if let Some(p) = path.parent() {
+            create_dir_all(p).context("creating the parent dir")?;
+        }
+        let mut file = File::create(&path)
+            .with_context(|| format!("creating {}", path.display()))?;
+        bincode::serialize_into(&mut file, &contents).context("serializing")?;
+        file.sync_all().context("syncing")?;
+        Ok(contents)
+    }))
+    .await
+}
+
 macro_rules! break_err {
     ($e:expr) => {
         match $e {
@@ -58,8 +101,11 @@ pub fn basename(path: &str) -> Option<&str> {
         .map(|osstr| osstr.to_str().expect("this is a subset of a rust string"))
 }
 
-pub fn dirname(path: &str) -> Option<&str> {
-    Path::new(path)
-        .parent()
-        .map(|osstr| osstr.to_str().expect("this is a subset of a rust string"))
+/// The absolute path of `path`, which is relative to `root` and starts with a slash.
+pub fn absolute(root: &str, path: &OsStr) -> OsString {
+    let mut abs = OsString::from(root);
+    if path != "/" {
+        abs.push(path);
+    }
+    abs
 }
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use protocol::util::{Normal, Percent};
+
+use crate::{
+    config,
+    util::{read_bincode, write_bincode},
+};
+
+/// Positions shorter than this are not worth asking about.
+const MIN_POSITION: Duration = Duration::from_secs(10);
+
+/// The last playback position of local files, keyed by the root directory and the path
+/// relative to it.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct WatchLater {
+    // NOTE: the paths are bytes since they might not be UTF-8
+    positions: HashMap<(String, Vec<u8>), Position>,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct Position {
+    progress: Duration,
+    length: Duration,
+}
+
+pub fn watch_later_file() -> PathBuf {
+    config::cache_dir().join("watch_later")
+}
+
+impl WatchLater {
+    pub async fn read(path: &Path) -> anyhow::Result<Self> {
+        read_bincode(path).await
+    }
+
+    pub async fn write(self, path: &Path) -> anyhow::Result<Self> {
+        write_bincode(path, self).await
+    }
+
+    pub fn position(&self, root: &str, path: &OsStr) -> Option<Duration> {
+        self.get(root, path).map(|pos| pos.progress)
+    }
+
+    /// How far into the file it was stopped
+    pub fn progress(&self, root: &str, path: &OsStr) -> Option<Percent<Normal>> {
+        self.get(root, path).and_then(|pos| {
+            Percent::of(pos.progress.as_secs_f64(), pos.length.as_secs_f64())
+        })
+    }
+
+    /// Remember where the file was stopped.
+    pub fn update(
+        &mut self,
+        root: &str,
+        path: &OsStr,
+        progress: Duration,
+        length: Duration,
+    ) {
+        let key = key(root, path);
+        if progress < MIN_POSITION {
+            self.positions.remove(&key);
+        } else {
+            self.positions.insert(key, Position { progress, length });
+        }
+    }
+
+    pub fn remove(&mut self, root: &str, path: &OsStr) {
+        self.positions.remove(&key(root, path));
+    }
+
+    fn get(&self, root: &str, path: &OsStr) -> Option<&Position> {
+        self.positions.get(&key(root, path))
+    }
+}
+
+fn key(root: &str, path: &OsStr) -> (String, Vec<u8>) {
+    (root.to_string(), path.as_bytes().to_vec())
+}
+
+/// If the file has been watched past the configured threshold
+pub fn is_finished(progress: Duration, length: Duration) -> bool {
+    is_past(progress, length, config::watched_threshold())
+}
+
+/// If `progress` is at least `threshold` percent of `length`
+fn is_past(progress: Duration, length: Duration, threshold: f64) -> bool {
+    !length.is_zero()
+        && progress.as_secs_f64() / length.as_secs_f64() * 100.0 >= threshold
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn past() {
+        let secs = Duration::from_secs;
+        assert!(is_past(secs(90), secs(100), 90.0));
+        assert!(is_past(secs(100), secs(100), 90.0));
+        assert!(!is_past(secs(89), secs(100), 90.0));
+        assert!(!is_past(secs(10), Duration::ZERO, 90.0));
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config,
+    util::{read_bincode, write_bincode},
+};
+
+/// Local files that have been watched, keyed by the root directory and the path relative
+/// to it. Kept separate from the file cache so that it survives refreshing it.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Watched {
+    // NOTE: the paths are bytes since they might not be UTF-8
+    files: HashSet<(String, Vec<u8>)>,
+}
+
+pub fn watched_file() -> PathBuf {
+    config::cache_dir().join("watched")
+}
+
+impl Watched {
+    pub async fn read(path: &Path) -> anyhow::Result<Self> {
+        read_bincode(path).await
+    }
+
+    pub async fn write(self, path: &Path) -> anyhow::Result<Self> {
+        write_bincode(path, self).await
+    }
+
+    pub fn is_watched(&self, root: &str, path: &OsStr) -> bool {
+        self.files.contains(&key(root, path))
+    }
+
+    pub fn set_watched(&mut self, root: &str, path: &OsStr, watched: bool) {
+        let key = key(root, path);
+        if watched {
+            self.files.insert(key);
+        } else {
+            self.files.remove(&key);
+        }
+    }
+
+    pub fn toggle(&mut self, root: &str, path: &OsStr) {
+        let watched = self.is_watched(root, path);
+        self.set_watched(root, path, !watched);
+    }
+}
+
+fn key(root: &str, path: &OsStr) -> (String, Vec<u8>) {
+    (root.to_string(), path.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toggling() {
+        let mut watched = Watched::default();
+        assert!(!watched.is_watched("/root", OsStr::new("/a")));
+        watched.toggle("/root", OsStr::new("/a"));
+        assert!(watched.is_watched("/root", OsStr::new("/a")));
+        assert!(!watched.is_watched("/other", OsStr::new("/a")));
+        watched.toggle("/root", OsStr::new("/a"));
+        assert!(!watched.is_watched("/root", OsStr::new("/a")));
+    }
+}